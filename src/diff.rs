@@ -0,0 +1,549 @@
+use crate::response::{ActionType, Application, Applications, Instance};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// A field of an instance that differs between two snapshots. Values are rendered as
+/// strings, `None` when the field or metadata key is not set.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    /// The field name, `metadata.<key>` for a metadata key
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// A difference between two registry snapshots
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum Change {
+    /// An application was added or deleted. Each of its instances is reported as well.
+    Application { app: String, action: ActionType },
+    /// An instance was added, deleted or modified; the changed fields are only set for a
+    /// modified instance
+    Instance {
+        app: String,
+        instance_id: String,
+        action: ActionType,
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// Every difference between two registry snapshots, in the order of the old snapshot
+/// followed by whatever only the new one has.
+///
+/// Its `Display` output is a report meant to be read during incidents:
+///
+/// ```text
+/// - app ORDER-SERVICE
+/// - ORDER-SERVICE/10.0.0.3
+/// ~ INVENTORY/10.0.0.1
+///     status: UP -> DOWN
+///     metadata.version: 1 -> 2
+/// + INVENTORY/10.0.0.2
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RegistryDiff {
+    pub changes: Vec<Change>,
+}
+
+impl RegistryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// What to compare when diffing snapshots
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiffOptions {
+    /// Ignore changes to `lastUpdatedTimestamp`, `lastDirtyTimestamp` and the lease
+    /// timestamps, which change on every heartbeat or re-registration
+    pub ignore_timestamps: bool,
+}
+
+impl DiffOptions {
+    /// Creates options comparing every field
+    pub fn new() -> DiffOptions {
+        DiffOptions::default()
+    }
+
+    pub fn ignore_timestamps(mut self, ignore: bool) -> DiffOptions {
+        self.ignore_timestamps = ignore;
+        self
+    }
+}
+
+/// Compares two registry snapshots. Applications are matched by name, ignoring case, and
+/// instances by [id](../response/struct.Instance.html#method.id).
+///
+/// # Arguments
+///
+/// * `old` - The earlier snapshot
+/// * `new` - The later snapshot
+/// * `options` - What to compare
+pub fn diff(old: &Applications, new: &Applications, options: &DiffOptions) -> RegistryDiff {
+    let old_apps = group(&old.applications);
+    let new_apps = group(&new.applications);
+    let mut changes = Vec::new();
+
+    for (key, (name, old_instances)) in old_apps.iter() {
+        match new_apps.get(key) {
+            None => {
+                changes.push(Change::Application {
+                    app: name.to_string(),
+                    action: ActionType::Deleted,
+                });
+                push_all(&mut changes, name, old_instances, ActionType::Deleted);
+            }
+            Some((_, new_instances)) => {
+                let old_by_id = by_id(old_instances);
+                let new_by_id = by_id(new_instances);
+                for old_instance in old_instances {
+                    match new_by_id.get(old_instance.id()) {
+                        None => {
+                            push_instance(&mut changes, name, old_instance, ActionType::Deleted)
+                        }
+                        Some(new_instance) => {
+                            let fields = compare(old_instance, new_instance, options);
+                            if !fields.is_empty() {
+                                changes.push(Change::Instance {
+                                    app: name.to_string(),
+                                    instance_id: old_instance.id().to_owned(),
+                                    action: ActionType::Modified,
+                                    fields,
+                                });
+                            }
+                        }
+                    }
+                }
+                for new_instance in new_instances {
+                    if !old_by_id.contains_key(new_instance.id()) {
+                        push_instance(&mut changes, name, new_instance, ActionType::Added);
+                    }
+                }
+            }
+        }
+    }
+
+    for (key, (name, new_instances)) in new_apps.iter() {
+        if old_apps.get(key).is_none() {
+            changes.push(Change::Application {
+                app: name.to_string(),
+                action: ActionType::Added,
+            });
+            push_all(&mut changes, name, new_instances, ActionType::Added);
+        }
+    }
+
+    RegistryDiff { changes }
+}
+
+// the instances of each app by normalized name, in snapshot order; applications sharing
+// a name are compared as one
+struct Grouped<'a> {
+    keys: Vec<String>,
+    apps: HashMap<String, (&'a str, Vec<&'a Instance>)>,
+}
+
+impl<'a> Grouped<'a> {
+    fn get(&self, key: &str) -> Option<&(&'a str, Vec<&'a Instance>)> {
+        self.apps.get(key)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&String, &(&'a str, Vec<&'a Instance>))> {
+        self.keys.iter().map(move |k| (k, &self.apps[k]))
+    }
+}
+
+fn group(applications: &[Application]) -> Grouped<'_> {
+    let mut grouped = Grouped {
+        keys: Vec::new(),
+        apps: HashMap::new(),
+    };
+    for application in applications {
+        let key = application.name.to_ascii_uppercase();
+        let (_, instances) = grouped.apps.entry(key.clone()).or_insert_with(|| {
+            grouped.keys.push(key);
+            (&application.name, Vec::new())
+        });
+        instances.extend(application.instance.iter());
+    }
+    grouped
+}
+
+fn by_id<'a>(instances: &[&'a Instance]) -> HashMap<&'a str, &'a Instance> {
+    instances.iter().map(|i| (i.id(), *i)).collect()
+}
+
+fn push_all(changes: &mut Vec<Change>, app: &str, instances: &[&Instance], action: ActionType) {
+    for instance in instances {
+        push_instance(changes, app, instance, action.clone());
+    }
+}
+
+fn push_instance(changes: &mut Vec<Change>, app: &str, instance: &Instance, action: ActionType) {
+    changes.push(Change::Instance {
+        app: app.to_owned(),
+        instance_id: instance.id().to_owned(),
+        action,
+        fields: Vec::new(),
+    });
+}
+
+fn compare(old: &Instance, new: &Instance, options: &DiffOptions) -> Vec<FieldChange> {
+    let mut fields = Vec::new();
+    let mut field = |name: &str, old: Option<String>, new: Option<String>| {
+        if old != new {
+            fields.push(FieldChange {
+                field: name.to_owned(),
+                old,
+                new,
+            });
+        }
+    };
+
+    field(
+        "status",
+        Some(String::from(&old.status)),
+        Some(String::from(&new.status)),
+    );
+    field(
+        "overriddenstatus",
+        old.overriddenstatus.as_ref().map(String::from),
+        new.overriddenstatus.as_ref().map(String::from),
+    );
+    field(
+        "hostName",
+        Some(old.host_name.clone()),
+        Some(new.host_name.clone()),
+    );
+    field(
+        "ipAddr",
+        Some(old.ip_addr.clone()),
+        Some(new.ip_addr.clone()),
+    );
+    field(
+        "vipAddress",
+        Some(old.vip_address.to_string()),
+        Some(new.vip_address.to_string()),
+    );
+    field(
+        "secureVipAddress",
+        Some(old.secure_vip_address.to_string()),
+        Some(new.secure_vip_address.to_string()),
+    );
+    field(
+        "port",
+        old.port.map(|p| p.to_string()),
+        new.port.map(|p| p.to_string()),
+    );
+    field(
+        "securePort",
+        old.secure_port.map(|p| p.to_string()),
+        new.secure_port.map(|p| p.to_string()),
+    );
+    field(
+        "homePageUrl",
+        Some(old.homepage_url.clone()),
+        Some(new.homepage_url.clone()),
+    );
+    field(
+        "statusPageUrl",
+        Some(old.status_page_url.clone()),
+        Some(new.status_page_url.clone()),
+    );
+    field(
+        "healthCheckUrl",
+        Some(old.health_check_url.clone()),
+        Some(new.health_check_url.clone()),
+    );
+    field(
+        "zone",
+        old.data_center_info.zone().map(str::to_owned),
+        new.data_center_info.zone().map(str::to_owned),
+    );
+
+    let keys = old
+        .metadata
+        .keys()
+        .chain(new.metadata.keys())
+        .collect::<BTreeSet<_>>();
+    for key in keys {
+        field(
+            &format!("metadata.{}", key),
+            old.metadata.get(key).map(render),
+            new.metadata.get(key).map(render),
+        );
+    }
+
+    if !options.ignore_timestamps {
+        field(
+            "lastUpdatedTimestamp",
+            Some(old.last_updated_timestamp.to_string()),
+            Some(new.last_updated_timestamp.to_string()),
+        );
+        field(
+            "lastDirtyTimestamp",
+            Some(old.last_dirty_timestamp.to_string()),
+            Some(new.last_dirty_timestamp.to_string()),
+        );
+        let lease = |i: &Instance| {
+            i.lease_info.as_ref().map(|l| {
+                format!(
+                    "registered {}, renewed {}, up since {}",
+                    l.registration_timestamp, l.last_renewal_timestamp, l.service_up_timestamp
+                )
+            })
+        };
+        field("leaseInfo", lease(old), lease(new));
+    }
+    fields
+}
+
+fn render(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn symbol(action: &ActionType) -> char {
+    match action {
+        ActionType::Added => '+',
+        ActionType::Deleted => '-',
+        ActionType::Modified => '~',
+    }
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            self.old.as_deref().unwrap_or("(none)"),
+            self.new.as_deref().unwrap_or("(none)")
+        )
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Application { app, action } => write!(f, "{} app {}", symbol(action), app),
+            Change::Instance {
+                app,
+                instance_id,
+                action,
+                fields,
+            } => {
+                write!(f, "{} {}/{}", symbol(action), app, instance_id)?;
+                for field in fields {
+                    write!(f, "\n    {}", field)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for RegistryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.changes.is_empty() {
+            return write!(f, "no changes");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{build_test_instance, Status};
+
+    fn instance(host: &str) -> Instance {
+        let mut instance = build_test_instance();
+        instance.host_name = host.to_owned();
+        instance
+    }
+
+    fn applications(applications: Vec<Application>) -> Applications {
+        Applications {
+            versions_delta: 1,
+            apps_hashcode: String::new(),
+            applications,
+        }
+    }
+
+    fn build_test_snapshots() -> (Applications, Applications) {
+        let old = applications(vec![
+            Application {
+                name: "INVENTORY".into(),
+                instance: vec![instance("a"), instance("b")],
+            },
+            Application {
+                name: "ORDER".into(),
+                instance: vec![instance("c")],
+            },
+        ]);
+
+        let mut modified = instance("a");
+        modified.status = Status::Down;
+        modified.port = Some(9090);
+        modified.metadata.remove("something");
+        modified
+            .metadata
+            .insert("version".to_owned(), Value::from(2));
+        let mut touched = instance("b");
+        touched.last_updated_timestamp += 1000;
+        let new = applications(vec![
+            Application {
+                name: "inventory".into(),
+                instance: vec![modified, touched, instance("d")],
+            },
+            Application {
+                name: "BILLING".into(),
+                instance: vec![instance("e")],
+            },
+        ]);
+        (old, new)
+    }
+
+    #[test]
+    fn test_diff() {
+        let (old, new) = build_test_snapshots();
+        let diff = diff(&old, &new, &DiffOptions::new().ignore_timestamps(true));
+
+        let expected = vec![
+            Change::Instance {
+                app: "INVENTORY".to_owned(),
+                instance_id: "a".to_owned(),
+                action: ActionType::Modified,
+                fields: vec![
+                    FieldChange {
+                        field: "status".to_owned(),
+                        old: Some("UP".to_owned()),
+                        new: Some("DOWN".to_owned()),
+                    },
+                    FieldChange {
+                        field: "port".to_owned(),
+                        old: Some("80".to_owned()),
+                        new: Some("9090".to_owned()),
+                    },
+                    FieldChange {
+                        field: "metadata.something".to_owned(),
+                        old: Some("somethingelse".to_owned()),
+                        new: None,
+                    },
+                    FieldChange {
+                        field: "metadata.version".to_owned(),
+                        old: None,
+                        new: Some("2".to_owned()),
+                    },
+                ],
+            },
+            Change::Instance {
+                app: "INVENTORY".to_owned(),
+                instance_id: "d".to_owned(),
+                action: ActionType::Added,
+                fields: Vec::new(),
+            },
+            Change::Application {
+                app: "ORDER".to_owned(),
+                action: ActionType::Deleted,
+            },
+            Change::Instance {
+                app: "ORDER".to_owned(),
+                instance_id: "c".to_owned(),
+                action: ActionType::Deleted,
+                fields: Vec::new(),
+            },
+            Change::Application {
+                app: "BILLING".to_owned(),
+                action: ActionType::Added,
+            },
+            Change::Instance {
+                app: "BILLING".to_owned(),
+                instance_id: "e".to_owned(),
+                action: ActionType::Added,
+                fields: Vec::new(),
+            },
+        ];
+        assert_eq!(expected, diff.changes);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let (old, new) = build_test_snapshots();
+        let diff = diff(&old, &new, &DiffOptions::new());
+        assert!(diff.changes.contains(&Change::Instance {
+            app: "INVENTORY".to_owned(),
+            instance_id: "b".to_owned(),
+            action: ActionType::Modified,
+            fields: vec![FieldChange {
+                field: "lastUpdatedTimestamp".to_owned(),
+                old: Some(
+                    old.applications[0].instance[1]
+                        .last_updated_timestamp
+                        .to_string()
+                ),
+                new: Some(
+                    new.applications[0].instance[1]
+                        .last_updated_timestamp
+                        .to_string()
+                ),
+            }],
+        }));
+
+        assert!(super::diff(&old, &old, &DiffOptions::new()).is_empty());
+    }
+
+    #[test]
+    fn test_report() {
+        let (old, new) = build_test_snapshots();
+        let report = diff(&old, &new, &DiffOptions::new().ignore_timestamps(true)).to_string();
+        assert_eq!(
+            "~ INVENTORY/a\n    status: UP -> DOWN\n    port: 80 -> 9090\n    \
+             metadata.something: somethingelse -> (none)\n    metadata.version: (none) -> 2\n\
+             + INVENTORY/d\n- app ORDER\n- ORDER/c\n+ app BILLING\n+ BILLING/e",
+            report
+        );
+        assert_eq!(
+            "no changes",
+            diff(&old, &old, &DiffOptions::new()).to_string()
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let diff = RegistryDiff {
+            changes: vec![
+                Change::Application {
+                    app: "ORDER".to_owned(),
+                    action: ActionType::Deleted,
+                },
+                Change::Instance {
+                    app: "INVENTORY".to_owned(),
+                    instance_id: "a".to_owned(),
+                    action: ActionType::Modified,
+                    fields: vec![FieldChange {
+                        field: "status".to_owned(),
+                        old: Some("UP".to_owned()),
+                        new: Some("DOWN".to_owned()),
+                    }],
+                },
+            ],
+        };
+        assert_eq!(
+            r#"{"changes":[{"application":{"app":"ORDER","action":"DELETED"}},{"instance":{"app":"INVENTORY","instanceId":"a","action":"MODIFIED","fields":[{"field":"status","old":"UP","new":"DOWN"}]}}]}"#,
+            serde_json::to_string(&diff).expect("test unwrap")
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::load_balancer::{base_url, is_up};
+use crate::outlier::OutlierDetector;
+use crate::registry::{CachedRegistry, DEFAULT_TTL};
+use crate::response::Instance;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{self, MissedTickBehavior};
+use tower::discover::Change;
+
+/// A [tower::discover::Discover](https://docs.rs/tower/latest/tower/discover/trait.Discover.html)
+/// over the `UP` instances of a Eureka app or VIP, keyed by instance id.
+///
+/// The registry is polled at a fixed interval and each snapshot is compared with the previous
+/// one: new instances are inserted, vanished or no longer `UP` instances are removed, and
+/// instances whose address changed are inserted again. Failed lookups are logged and the
+/// last known instances are kept until the next poll.
+///
+/// With an [OutlierDetector](../outlier/struct.OutlierDetector.html), instances it ejected
+/// are removed at the next poll and inserted again once their ejection ends. Callers feed
+/// the detector with the outcome of their calls through a clone of it.
+pub struct EurekaDiscover<S> {
+    // built on the first poll, once the discover is configured
+    state: Option<DiscoverState<S>>,
+    changes: Option<BoxStream<'static, Result<Change<String, S>, Infallible>>>,
+}
+
+type MakeService<S> = Box<dyn Fn(&Instance) -> S + Send>;
+
+struct DiscoverState<S> {
+    registry: Arc<CachedRegistry>,
+    name: String,
+    make_service: MakeService<S>,
+    outlier_detector: Option<OutlierDetector>,
+    // instance id -> base url of every instance handed out so far
+    known: HashMap<String, String>,
+    pending: VecDeque<Change<String, S>>,
+    interval: time::Interval,
+}
+
+impl<S: Send + 'static> EurekaDiscover<S> {
+    /// Creates a new discover that polls at the registry's default ttl
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The registry used to look up the instances
+    /// * `name` - The app or VIP name
+    /// * `make_service` - Builds the service used to reach an instance
+    pub fn new<F>(registry: Arc<CachedRegistry>, name: &str, make_service: F) -> EurekaDiscover<S>
+    where
+        F: Fn(&Instance) -> S + Send + 'static,
+    {
+        EurekaDiscover::with_interval(registry, name, DEFAULT_TTL, make_service)
+    }
+
+    /// Creates a new discover that polls the registry every `interval`
+    pub fn with_interval<F>(
+        registry: Arc<CachedRegistry>,
+        name: &str,
+        interval: Duration,
+        make_service: F,
+    ) -> EurekaDiscover<S>
+    where
+        F: Fn(&Instance) -> S + Send + 'static,
+    {
+        let mut interval = time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        EurekaDiscover {
+            state: Some(DiscoverState {
+                registry,
+                name: name.to_owned(),
+                make_service: Box::new(make_service),
+                outlier_detector: None,
+                known: HashMap::new(),
+                pending: VecDeque::new(),
+                interval,
+            }),
+            changes: None,
+        }
+    }
+
+    /// Leaves out the instances the detector ejected
+    pub fn outlier_detector(mut self, detector: OutlierDetector) -> EurekaDiscover<S> {
+        if let Some(state) = self.state.as_mut() {
+            state.outlier_detector = Some(detector);
+        }
+        self
+    }
+}
+
+fn changes<S: Send + 'static>(
+    state: DiscoverState<S>,
+) -> BoxStream<'static, Result<Change<String, S>, Infallible>> {
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(change) = state.pending.pop_front() {
+                return Some((Ok(change), state));
+            }
+            state.interval.tick().await;
+            match state.registry.resolve(&state.name).await {
+                Ok(app) => {
+                    let up = app
+                        .iter()
+                        .flat_map(|a| a.instance.iter())
+                        .filter(|i| is_up(i))
+                        .collect::<Vec<_>>();
+                    let candidates = match &state.outlier_detector {
+                        Some(detector) => detector.filter(&up),
+                        None => up,
+                    };
+                    for change in diff(&mut state.known, &candidates) {
+                        state.pending.push_back(match change {
+                            Change::Insert(id, i) => Change::Insert(id, (state.make_service)(i)),
+                            Change::Remove(id) => Change::Remove(id),
+                        });
+                    }
+                }
+                Err(e) => warn!("failed to discover instances of {}: {}", state.name, e),
+            }
+        }
+    })
+    .boxed()
+}
+
+// the state and the boxed stream are never pinned in place
+impl<S> Unpin for EurekaDiscover<S> {}
+
+impl<S: Send + 'static> Stream for EurekaDiscover<S> {
+    type Item = Result<Change<String, S>, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(state) = self.state.take() {
+            self.changes = Some(changes(state));
+        }
+        match self.changes.as_mut() {
+            Some(changes) => changes.poll_next_unpin(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// Compares the instances to serve with those known so far, updating `known` and returning
+/// the removals followed by the insertions
+fn diff<'a>(
+    known: &mut HashMap<String, String>,
+    instances: &[&'a Instance],
+) -> Vec<Change<String, &'a Instance>> {
+    let current = instances
+        .iter()
+        .filter_map(|i| base_url(i, false).map(|url| (i.id().to_owned(), (url, *i))))
+        .collect::<HashMap<_, _>>();
+
+    let mut changes = known
+        .keys()
+        .filter(|id| !current.contains_key(*id))
+        .map(|id| Change::Remove(id.clone()))
+        .collect::<Vec<_>>();
+    known.retain(|id, _| current.contains_key(id));
+
+    for (id, (url, instance)) in current {
+        if known.get(&id) != Some(&url) {
+            known.insert(id.clone(), url);
+            changes.push(Change::Insert(id, instance));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::build_test_instance;
+
+    fn build_test_instance_with_id(id: &str) -> Instance {
+        let mut instance = build_test_instance();
+        instance.instance_id = Some(id.to_owned());
+        instance
+    }
+
+    fn summarize(changes: Vec<Change<String, &Instance>>) -> Vec<String> {
+        let mut summary = changes
+            .into_iter()
+            .map(|c| match c {
+                Change::Insert(id, _) => format!("+{}", id),
+                Change::Remove(id) => format!("-{}", id),
+            })
+            .collect::<Vec<_>>();
+        summary.sort();
+        summary
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut known = HashMap::new();
+        let mut snapshot = vec![
+            build_test_instance_with_id("a"),
+            build_test_instance_with_id("b"),
+        ];
+        let all = snapshot.iter().collect::<Vec<_>>();
+        assert_eq!(vec!["+a", "+b"], summarize(diff(&mut known, &all)));
+        assert!(diff(&mut known, &all).is_empty());
+
+        // b is no longer served, a moves to another port and c arrives
+        snapshot[0].port = Some(8080);
+        snapshot.push(build_test_instance_with_id("c"));
+        let served = [&snapshot[0], &snapshot[2]];
+        assert_eq!(vec!["+a", "+c", "-b"], summarize(diff(&mut known, &served)));
+
+        assert_eq!(vec!["-a", "-c"], summarize(diff(&mut known, &[])));
+        assert!(known.is_empty());
+    }
+}
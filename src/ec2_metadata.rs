@@ -0,0 +1,196 @@
+use crate::errors::EurekaClientError;
+use crate::request::{AmazonMetaData, DataCenterInfo, DcName, Instance};
+use futures::future::try_join_all;
+use reqwest::{Client, StatusCode};
+use std::time::Duration;
+
+/// The link-local address of the EC2 instance metadata service
+pub const DEFAULT_METADATA_URL: &str = "http://169.254.169.254";
+
+const TOKEN_PATH: &str = "/latest/api/token";
+const META_DATA_PATH: &str = "/latest/meta-data/";
+const IDENTITY_DOCUMENT_PATH: &str = "/latest/dynamic/instance-identity/document";
+const TOKEN_HEADER: &str = "X-aws-ec2-metadata-token";
+const TOKEN_TTL_HEADER: &str = "X-aws-ec2-metadata-token-ttl-seconds";
+const TOKEN_TTL_SECS: &str = "21600";
+
+// metadata service paths, in AmazonMetaData field order
+const KEYS: &[&str] = &[
+    "ami-launch-index",
+    "local-hostname",
+    "placement/availability-zone",
+    "instance-id",
+    "public-ipv4",
+    "public-hostname",
+    "ami-manifest-path",
+    "local-ipv4",
+    "hostname",
+    "ami-id",
+    "instance-type",
+    "ipv6",
+    "mac",
+];
+
+/// Reads [AmazonMetaData](../request/struct.AmazonMetaData.html) from the EC2 instance
+/// metadata service, using IMDSv2 session tokens when the service supports them.
+pub struct Ec2MetadataProvider {
+    client: Client,
+    base_url: String,
+}
+
+impl Ec2MetadataProvider {
+    /// Creates a provider for the standard metadata service address
+    pub fn new() -> Result<Ec2MetadataProvider, EurekaClientError> {
+        Ec2MetadataProvider::with_base_url(DEFAULT_METADATA_URL)
+    }
+
+    /// Creates a provider for the given base url, e.g. a local stub during tests
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The metadata service url, without the `/latest/...` path
+    pub fn with_base_url(base_url: &str) -> Result<Ec2MetadataProvider, EurekaClientError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(2))
+            .build()
+            .map_err(EurekaClientError::from)?;
+        Ok(Ec2MetadataProvider {
+            client,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        })
+    }
+
+    /// Fetches every `AmazonMetaData` field from the metadata service.
+    /// Required keys the service does not publish for this instance are left empty.
+    pub async fn fetch(&self) -> Result<AmazonMetaData, EurekaClientError> {
+        let token = self.token().await?;
+        let token = token.as_deref();
+        let mut values = try_join_all(KEYS.iter().map(|k| self.get(token, k)))
+            .await?
+            .into_iter();
+        let mut next = || values.next().flatten();
+
+        let mut metadata = AmazonMetaData {
+            ami_launch_index: next(),
+            local_hostname: next().unwrap_or_default(),
+            availability_zone: next().unwrap_or_default(),
+            instance_id: next().unwrap_or_default(),
+            public_ip4: next(),
+            public_hostname: next(),
+            ami_manifest_path: next(),
+            local_ip4: next().unwrap_or_default(),
+            hostname: next(),
+            ami_id: next().unwrap_or_default(),
+            instance_type: next().unwrap_or_default(),
+            ipv6: next(),
+            mac: next(),
+            ..Default::default()
+        };
+
+        if let Some(mac) = &metadata.mac {
+            let key = format!("network/interfaces/macs/{}/vpc-id", mac);
+            metadata.vpc_id = self.get(token, &key).await?;
+        }
+        metadata.account_id = self.account_id(token).await?;
+        Ok(metadata)
+    }
+
+    /// Fetches the metadata and stores it as an Amazon `DataCenterInfo` on the instance
+    ///
+    /// # Arguments
+    ///
+    /// * `instance` - The instance to update
+    /// * `set_host_and_ip` - Also replace the host name and ip address with
+    ///   `local-hostname` and `local-ipv4`
+    pub async fn populate(
+        &self,
+        instance: &mut Instance,
+        set_host_and_ip: bool,
+    ) -> Result<(), EurekaClientError> {
+        let metadata = self.fetch().await?;
+        if set_host_and_ip {
+            if !metadata.local_hostname.is_empty() {
+                instance.host_name = metadata.local_hostname.clone();
+            }
+            if !metadata.local_ip4.is_empty() {
+                instance.ip_addr = metadata.local_ip4.clone();
+            }
+        }
+        instance.data_center_info = DataCenterInfo {
+            name: DcName::Amazon,
+            metadata: Some(metadata),
+            custom_metadata: None,
+        };
+        Ok(())
+    }
+
+    /// Requests an IMDSv2 session token, returning None when the service only speaks IMDSv1
+    async fn token(&self) -> Result<Option<String>, EurekaClientError> {
+        let url = format!("{}{}", self.base_url, TOKEN_PATH);
+        let res = self
+            .client
+            .put(url)
+            .header(TOKEN_TTL_HEADER, TOKEN_TTL_SECS)
+            .send()
+            .await?;
+
+        let status = res.status();
+        if status.is_success() {
+            Ok(Some(res.text().await?))
+        } else {
+            debug!(
+                "metadata token request returned {:?}, falling back to IMDSv1",
+                status
+            );
+            Ok(None)
+        }
+    }
+
+    /// The account id is only published in the instance identity document
+    async fn account_id(&self, token: Option<&str>) -> Result<Option<String>, EurekaClientError> {
+        let url = format!("{}{}", self.base_url, IDENTITY_DOCUMENT_PATH);
+        let document = match self.send(&url, token).await? {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let document: serde_json::Value = serde_json::from_str(&document)?;
+        Ok(document
+            .get("accountId")
+            .and_then(|v| v.as_str())
+            .map(str::to_owned))
+    }
+
+    async fn get(
+        &self,
+        token: Option<&str>,
+        key: &str,
+    ) -> Result<Option<String>, EurekaClientError> {
+        let url = format!("{}{}{}", self.base_url, META_DATA_PATH, key);
+        self.send(&url, token).await
+    }
+
+    /// Returns None when the service does not publish the requested path
+    async fn send(
+        &self,
+        url: &str,
+        token: Option<&str>,
+    ) -> Result<Option<String>, EurekaClientError> {
+        let mut req = self.client.get(url);
+        if let Some(t) = token {
+            req = req.header(TOKEN_HEADER, t);
+        }
+        let res = req.send().await?;
+
+        match res.status() {
+            s if s.is_success() => Ok(Some(res.text().await?.trim().to_owned())),
+            StatusCode::NOT_FOUND => {
+                debug!("metadata path {} is not available", url);
+                Ok(None)
+            }
+            s => Err(EurekaClientError::GenericError(format!(
+                "Metadata service returned {} for {}",
+                s, url
+            ))),
+        }
+    }
+}
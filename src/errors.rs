@@ -1,6 +1,8 @@
+use crate::request::{join_errors, ValidationError};
 use serde_json::error::Error as ParserError;
 use std::error::Error;
 use std::fmt::{self, Display};
+use std::io;
 use url::ParseError;
 
 use self::EurekaClientError::*;
@@ -22,6 +24,12 @@ pub enum EurekaClientError {
     BadRequest,
     /// The specified resource does not exist in eureka, such as an invalid application name
     NotFound,
+    /// The registration was rejected locally before being sent to Eureka
+    ValidationError(Vec<ValidationError>),
+    /// The response body exceeded the configured maximum size, in bytes
+    ResponseTooLarge(usize),
+    /// An error occurred reading or writing a local file
+    IoError(io::Error),
 }
 
 impl Error for EurekaClientError {
@@ -30,6 +38,7 @@ impl Error for EurekaClientError {
             ClientError(ref error) => Some(error),
             JsonError(ref error) => Some(error),
             InvalidUri(ref error) => Some(error),
+            IoError(ref error) => Some(error),
             _ => None,
         }
     }
@@ -47,6 +56,12 @@ impl From<ParserError> for EurekaClientError {
     }
 }
 
+impl From<io::Error> for EurekaClientError {
+    fn from(err: io::Error) -> EurekaClientError {
+        IoError(err)
+    }
+}
+
 impl From<ParseError> for EurekaClientError {
     fn from(err: ParseError) -> EurekaClientError {
         InvalidUri(err)
@@ -63,6 +78,11 @@ impl Display for EurekaClientError {
             InternalServerError => write!(f, "Internal server error (500)"),
             BadRequest => write!(f, "Bad request (400)"),
             NotFound => write!(f, "Not found (404)"),
+            ValidationError(errors) => write!(f, "Invalid registration: {}", join_errors(errors)),
+            IoError(e) => write!(f, "I/O error: {}", e),
+            ResponseTooLarge(limit) => {
+                write!(f, "Response exceeded the maximum size of {} bytes", limit)
+            }
         }
     }
 }
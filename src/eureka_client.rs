@@ -1,18 +1,26 @@
 use crate::errors::EurekaClientError;
-use crate::request::RegisterRequest;
-use crate::response::{ApplicationResponse, ApplicationsResponse};
+use crate::region::RegionConfig;
+use crate::request::{RegisterRequest, Status};
+use crate::response::{self, ApplicationResponse, ApplicationsResponse};
+use crate::streaming::{ApplicationStream, StreamOptions};
+use crate::zone::ZoneConfig;
 use reqwest::header::{
     HeaderMap, HeaderValue, ACCEPT, ACCEPT_CHARSET, ACCEPT_ENCODING, CONTENT_TYPE, USER_AGENT,
 };
 use reqwest::{Client, StatusCode, Url};
 use serde_json;
+use std::collections::HashSet;
+use std::sync::Mutex;
 use std::time::Duration;
 
 /// A client for accessing Eureka
 pub struct EurekaClient {
     client: Client,
     client_name: String,
-    eureka_cluster_url: String,
+    eureka_cluster_urls: Vec<String>,
+    // application urls that accepted a registration or heartbeat, so a 404 from them
+    // means the server forgot the instance rather than that the path is wrong
+    lease_urls: Mutex<HashSet<Url>>,
 }
 
 //
@@ -40,10 +48,37 @@ impl EurekaClient {
         Ok(EurekaClient {
             client,
             client_name: client_name.to_owned(),
-            eureka_cluster_url: eureka_cluster_url.to_owned(),
+            eureka_cluster_urls: vec![eureka_cluster_url.to_owned()],
+            lease_urls: Mutex::new(HashSet::new()),
         })
     }
 
+    /// Creates a new instance of EurekaClient that tries the Eureka servers of its own
+    /// zone first, falling back to the servers of the other zones
+    ///
+    /// # Arguments
+    ///
+    /// * `client_name` - The name of this client
+    /// * `zones` - The zone configuration holding the server urls of each zone
+    pub fn with_zones(
+        client_name: &str,
+        zones: &ZoneConfig,
+    ) -> Result<EurekaClient, EurekaClientError> {
+        let urls = zones.ordered_service_urls();
+        debug!(
+            "Creating new zone aware Eureka Client client_name:{:?}, local_zone:{:?}, urls:{:?}",
+            client_name, zones.local_zone, urls
+        );
+        if urls.is_empty() {
+            return Err(EurekaClientError::GenericError(
+                "No service urls configured".to_string(),
+            ));
+        }
+        let mut client = EurekaClient::new(client_name, &urls[0])?;
+        client.eureka_cluster_urls = urls;
+        Ok(client)
+    }
+
     pub async fn register(
         &self,
         application_id: &str,
@@ -53,6 +88,10 @@ impl EurekaClient {
             "register: application_id={:?}, register_request:{:?}",
             application_id, register_request
         );
+        register_request
+            .validate()
+            .map_err(EurekaClientError::ValidationError)?;
+
         // build both /v2 and non-/v2 variants; build_uris will produce candidate URIs
         let _path = format!("/v2/apps/{}", application_id);
 
@@ -68,9 +107,12 @@ impl EurekaClient {
         } else {
             String::new()
         };
-        let dci_xml = match inst.data_center_info.name {
-            crate::request::DcName::MyOwn => "<dataCenterInfo class=\"com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo\"><name>MyOwn</name></dataCenterInfo>".to_string(),
-            crate::request::DcName::Amazon => "<dataCenterInfo class=\"com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo\"><name>Amazon</name></dataCenterInfo>".to_string(),
+        let dci_metadata = match serde_json::to_value(&inst.data_center_info)? {
+            serde_json::Value::Object(mut dci) => match dci.remove("metadata") {
+                Some(serde_json::Value::Object(metadata)) => metadata,
+                _ => serde_json::Map::new(),
+            },
+            _ => serde_json::Map::new(),
         };
         let lease_xml = if let Some(li) = &inst.lease_info {
             let eviction = li.eviction_duration_in_secs.unwrap_or(90);
@@ -82,45 +124,63 @@ impl EurekaClient {
             String::new()
         };
 
-        let xml = format!(
-            "<instance>\n  <hostName>{}</hostName>\n  <app>{}</app>\n  <ipAddr>{}</ipAddr>\n  <vipAddress>{}</vipAddress>\n  <secureVipAddress>{}</secureVipAddress>\n  <status>{}</status>\n  {}\n  {}\n  <countryId>1</countryId>\n  {}\n  {}\n  <metadata class=\"java.util.Collections$EmptyMap\"/>\n  <homePageUrl>{}</homePageUrl>\n  <statusPageUrl>{}</statusPageUrl>\n  <healthCheckUrl>{}</healthCheckUrl>\n</instance>",
-            inst.host_name,
-            inst.app,
-            inst.ip_addr,
-            inst.vip_address,
-            inst.secure_vip_address,
-            String::from(&inst.status),
-            port_xml,
-            secure_port_xml,
-            dci_xml,
-            lease_xml,
-            inst.homepage_url,
-            inst.status_page_url,
-            inst.health_check_url
-        );
+        // metadata keys become element names, so keys that are not valid XML names can
+        // only be sent as JSON
+        let dci_metadata_xml = if dci_metadata.is_empty() {
+            Some(String::new())
+        } else {
+            xml_metadata(&dci_metadata)
+        };
+        let xml = match (dci_metadata_xml, xml_metadata(&inst.metadata)) {
+            (Some(dci_metadata_xml), Some(metadata_xml)) => Some(format!(
+                "<instance>\n  <hostName>{}</hostName>\n  <app>{}</app>\n  <ipAddr>{}</ipAddr>\n  <vipAddress>{}</vipAddress>\n  <secureVipAddress>{}</secureVipAddress>\n  <status>{}</status>\n  {}\n  {}\n  <countryId>1</countryId>\n  <dataCenterInfo class=\"{}\"><name>{}</name>{}</dataCenterInfo>\n  {}\n  {}\n  <homePageUrl>{}</homePageUrl>\n  <statusPageUrl>{}</statusPageUrl>\n  <healthCheckUrl>{}</healthCheckUrl>\n</instance>",
+                xml_escape(&inst.host_name),
+                xml_escape(&inst.app),
+                xml_escape(&inst.ip_addr),
+                xml_escape(&inst.vip_address),
+                xml_escape(&inst.secure_vip_address),
+                String::from(&inst.status),
+                port_xml,
+                secure_port_xml,
+                inst.data_center_info.class(),
+                xml_escape(&String::from(&inst.data_center_info.name)),
+                dci_metadata_xml,
+                lease_xml,
+                metadata_xml,
+                xml_escape(&inst.homepage_url),
+                xml_escape(&inst.status_page_url),
+                xml_escape(&inst.health_check_url)
+            )),
+            _ => None,
+        };
 
         // Prefer the /eureka/apps endpoint first (observed to succeed on Spring Cloud Eureka instances).
-        let base = &self.eureka_cluster_url;
         // Prefer known working endpoints ordering (try v2 variant first)
-        let candidates = vec![
-            format!("{}/eureka/v2/apps/{}", base, application_id),
-            format!("{}/eureka/apps/{}", base, application_id),
-            format!("{}/v2/apps/{}", base, application_id),
-            format!("{}/apps/{}", base, application_id),
-        ];
+        let candidates = self
+            .eureka_cluster_urls
+            .iter()
+            .flat_map(|base| {
+                [
+                    format!("{}/eureka/v2/apps/{}", base, application_id),
+                    format!("{}/eureka/apps/{}", base, application_id),
+                    format!("{}/v2/apps/{}", base, application_id),
+                    format!("{}/apps/{}", base, application_id),
+                ]
+            })
+            .collect::<Vec<_>>();
 
         let mut last_err: Option<EurekaClientError> = None;
         let mut saw_bad_request = false;
         let mut saw_internal_server_error = false;
 
         // Try XML registration first (Spring Cloud Eureka often expects XML)
-        for uri_str in &candidates {
+        for uri_str in candidates.iter().filter(|_| xml.is_some()) {
             let url = Url::parse(uri_str).map_err(EurekaClientError::from)?;
             let res = match self
                 .client
                 .post(url.clone())
                 .header("content-type", "application/xml")
-                .body(xml.clone())
+                .body(xml.clone().unwrap_or_default())
                 .send()
                 .await
             {
@@ -136,6 +196,7 @@ impl EurekaClient {
             let _body_str = String::from_utf8_lossy(&body_bytes);
 
             if status.is_success() {
+                self.remember_lease_url(url);
                 return Ok(());
             }
 
@@ -212,22 +273,11 @@ impl EurekaClient {
             "healthCheckUrl".to_string(),
             serde_json::Value::String(inst.health_check_url.clone()),
         );
-        // dataCenterInfo with @class and name
-        let mut dci = serde_json::Map::new();
-        dci.insert(
-            "@class".to_string(),
-            serde_json::Value::String(
-                "com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo".to_string(),
-            ),
-        );
-        dci.insert(
-            "name".to_string(),
-            serde_json::Value::String(match inst.data_center_info.name {
-                crate::request::DcName::MyOwn => "MyOwn".to_string(),
-                crate::request::DcName::Amazon => "Amazon".to_string(),
-            }),
+        // dataCenterInfo with @class, name and any Amazon or custom metadata
+        inst_map.insert(
+            "dataCenterInfo".to_string(),
+            serde_json::to_value(&inst.data_center_info)?,
         );
-        inst_map.insert("dataCenterInfo".to_string(), serde_json::Value::Object(dci));
 
         // lease
         if let Some(li) = &inst.lease_info {
@@ -241,13 +291,18 @@ impl EurekaClient {
             inst_map.insert("leaseInfo".to_string(), serde_json::Value::Object(lease));
         }
 
-        // metadata (top-level) - send Java empty map marker
-        let mut meta_obj = serde_json::Map::new();
-        meta_obj.insert(
-            "@class".to_string(),
-            serde_json::Value::String("java.util.Collections$EmptyMap".to_string()),
-        );
-        inst_map.insert("metadata".to_string(), serde_json::Value::Object(meta_obj));
+        // metadata (top-level) - send the Java empty map marker when there is none
+        let metadata = if inst.metadata.is_empty() {
+            let mut meta_obj = serde_json::Map::new();
+            meta_obj.insert(
+                "@class".to_string(),
+                serde_json::Value::String("java.util.Collections$EmptyMap".to_string()),
+            );
+            meta_obj
+        } else {
+            inst.metadata.clone()
+        };
+        inst_map.insert("metadata".to_string(), serde_json::Value::Object(metadata));
         // countryId
         inst_map.insert(
             "countryId".to_string(),
@@ -293,7 +348,10 @@ impl EurekaClient {
                     // Try next URI
                     continue;
                 }
-                _ => return Ok(()),
+                _ => {
+                    self.remember_lease_url(url);
+                    return Ok(());
+                }
             }
         }
 
@@ -390,6 +448,204 @@ impl EurekaClient {
         }
     }
 
+    /// Renews the lease of a registered instance. `NotFound` means Eureka no longer knows
+    /// the instance, which should then register again: it is returned when a server
+    /// answers 404 on a path that accepted this app's registration or heartbeats before,
+    /// whatever the other servers answered, or when every server answers 404.
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The app the instance is registered under
+    /// * `instance_id` - The instance id, which is the host name unless one was set
+    pub async fn heartbeat(
+        &self,
+        application_id: &str,
+        instance_id: &str,
+    ) -> Result<(), EurekaClientError> {
+        let base_path = format!("/v2/apps/{}", application_id);
+        let mut last_err: Option<EurekaClientError> = None;
+        let mut forgotten = false;
+
+        for app_url in self.build_uris(&base_path)? {
+            let mut instance_url = app_url.clone();
+            match instance_url.path_segments_mut() {
+                Ok(mut segments) => {
+                    segments.push(instance_id);
+                }
+                Err(e) => {
+                    last_err = Some(EurekaClientError::GenericError(format!(
+                        "Failed to append instance id to URL {e:?}"
+                    )));
+                    continue;
+                }
+            }
+
+            let res = match self
+                .client
+                .put(instance_url.clone())
+                .headers(self.headers_map())
+                .send()
+                .await
+            {
+                Ok(r) => r,
+                Err(e) => {
+                    last_err = Some(EurekaClientError::from(e));
+                    continue;
+                }
+            };
+
+            let status = res.status();
+            debug!(
+                "heartbeat: server response status={:?} for url={}",
+                status, instance_url
+            );
+            match status {
+                s if s.is_success() => {
+                    self.remember_lease_url(app_url);
+                    return Ok(());
+                }
+                // a 404 only says the instance is gone when the path served it before,
+                // otherwise the path may just not be mounted on this server
+                StatusCode::NOT_FOUND => forgotten |= self.is_lease_url(&app_url),
+                StatusCode::BAD_REQUEST => last_err = Some(EurekaClientError::BadRequest),
+                s if s.is_server_error() => last_err = Some(EurekaClientError::InternalServerError),
+                s => {
+                    last_err = Some(EurekaClientError::GenericError(format!(
+                        "Unexpected status {} renewing lease",
+                        s
+                    )))
+                }
+            }
+        }
+
+        // a server that forgot the instance wins over any other failure, so the caller
+        // registers again
+        match last_err {
+            Some(e) if !forgotten => Err(e),
+            _ => Err(EurekaClientError::NotFound),
+        }
+    }
+
+    /// Sets the status of a registered instance through Eureka's status endpoint,
+    /// e.g. to switch a `STARTING` instance to `UP`
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The app the instance is registered under
+    /// * `instance_id` - The instance id, which is the host name unless one was set
+    /// * `status` - The new status
+    pub async fn update_status(
+        &self,
+        application_id: &str,
+        instance_id: &str,
+        status: &Status,
+    ) -> Result<(), EurekaClientError> {
+        let base_path = format!("/v2/apps/{}", application_id);
+        let value = String::from(status);
+        let mut last_err: Option<EurekaClientError> = None;
+
+        for app_url in self.build_uris(&base_path)? {
+            let mut status_url = app_url.clone();
+            match status_url.path_segments_mut() {
+                Ok(mut segments) => {
+                    segments.push(instance_id).push("status");
+                }
+                Err(e) => {
+                    last_err = Some(EurekaClientError::GenericError(format!(
+                        "Failed to append instance id to URL {e:?}"
+                    )));
+                    continue;
+                }
+            }
+            status_url.query_pairs_mut().append_pair("value", &value);
+
+            let res = match self
+                .client
+                .put(status_url.clone())
+                .headers(self.headers_map())
+                .send()
+                .await
+            {
+                Ok(r) => r,
+                Err(e) => {
+                    last_err = Some(EurekaClientError::from(e));
+                    continue;
+                }
+            };
+
+            let status = res.status();
+            debug!(
+                "update_status: server response status={:?} for url={}",
+                status, status_url
+            );
+            match status {
+                s if s.is_success() => return Ok(()),
+                StatusCode::NOT_FOUND => continue,
+                StatusCode::BAD_REQUEST => last_err = Some(EurekaClientError::BadRequest),
+                s if s.is_server_error() => last_err = Some(EurekaClientError::InternalServerError),
+                s => {
+                    last_err = Some(EurekaClientError::GenericError(format!(
+                        "Unexpected status {} updating status",
+                        s
+                    )))
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or(EurekaClientError::NotFound))
+    }
+
+    /// Gets the status override set remotely on an instance, e.g. by an operator taking it
+    /// `OUT_OF_SERVICE`, or `None` when no override is active
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The app the instance is registered under
+    /// * `instance_id` - The instance id, which is the host name unless one was set
+    pub async fn get_status_override(
+        &self,
+        application_id: &str,
+        instance_id: &str,
+    ) -> Result<Option<response::Status>, EurekaClientError> {
+        let app = match self.get_application(application_id).await {
+            Ok(res) => res.application,
+            Err(EurekaClientError::NotFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(app
+            .instance
+            .into_iter()
+            .find(|i| i.id() == instance_id)
+            .and_then(|i| i.overriddenstatus)
+            .filter(|s| *s != response::Status::Unknown))
+    }
+
+    /// Returns the active override that pushing `status` would clobber. Like Eureka, an
+    /// instance may still report itself `DOWN` or `STARTING`, and a failed lookup lets the
+    /// status through.
+    pub(crate) async fn conflicting_override(
+        &self,
+        application_id: &str,
+        instance_id: &str,
+        status: &Status,
+    ) -> Option<response::Status> {
+        if matches!(status, Status::Down | Status::Starting) {
+            return None;
+        }
+        match self.get_status_override(application_id, instance_id).await {
+            Ok(status_override) => {
+                status_override.filter(|o| String::from(o) != String::from(status))
+            }
+            Err(e) => {
+                debug!(
+                    "failed to look up the status override of {}: {}",
+                    instance_id, e
+                );
+                None
+            }
+        }
+    }
+
     pub async fn get_application(
         &self,
         application_id: &str,
@@ -424,6 +680,10 @@ impl EurekaClient {
                 // try next URI
                 continue;
             }
+            if !status.is_success() {
+                last_err = Some(status_error(status));
+                continue;
+            }
 
             let body_bytes = res.bytes().await.map_err(EurekaClientError::from)?;
 
@@ -439,8 +699,113 @@ impl EurekaClient {
     }
 
     pub async fn get_applications(&self) -> Result<ApplicationsResponse, EurekaClientError> {
-        let path = "/v2/apps";
+        self.fetch_applications("/v2/apps")
+            .await
+            .map(|(apps, _)| apps)
+    }
+
+    /// Fetches every application along with the url of the server that answered
+    pub async fn get_applications_with_source(
+        &self,
+    ) -> Result<(ApplicationsResponse, Url), EurekaClientError> {
+        self.fetch_applications("/v2/apps").await
+    }
+
+    /// Fetches the instances added, modified or deleted in the last few minutes,
+    /// each marked with its `actionType`
+    pub async fn get_delta(&self) -> Result<ApplicationsResponse, EurekaClientError> {
+        self.fetch_applications("/v2/apps/delta")
+            .await
+            .map(|(apps, _)| apps)
+    }
+
+    /// Fetches every application of the local region and of the configured remote regions,
+    /// with each instance tagged with its region and the local region's instances first
+    ///
+    /// # Arguments
+    ///
+    /// * `regions` - The local region and the remote regions to fetch
+    pub async fn get_applications_in_regions(
+        &self,
+        regions: &RegionConfig,
+    ) -> Result<ApplicationsResponse, EurekaClientError> {
+        self.fetch_in_regions("/v2/apps", regions).await
+    }
 
+    /// Fetches the delta of the local region and of the configured remote regions,
+    /// tagged like [get_applications_in_regions](#method.get_applications_in_regions)
+    pub async fn get_delta_in_regions(
+        &self,
+        regions: &RegionConfig,
+    ) -> Result<ApplicationsResponse, EurekaClientError> {
+        self.fetch_in_regions("/v2/apps/delta", regions).await
+    }
+
+    async fn fetch_in_regions(
+        &self,
+        path: &str,
+        regions: &RegionConfig,
+    ) -> Result<ApplicationsResponse, EurekaClientError> {
+        let path = match regions.query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path.to_owned(),
+        };
+        let (mut res, _) = self.fetch_applications(&path).await?;
+        regions.tag(&mut res.applications);
+        Ok(res)
+    }
+
+    /// Streams every application as it is parsed from the response, without buffering the
+    /// whole registry. Applications and instances can be filtered out while parsing.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The maximum response size and the filters to apply
+    pub async fn stream_applications(
+        &self,
+        options: StreamOptions,
+    ) -> Result<ApplicationStream, EurekaClientError> {
+        let mut last_err: Option<EurekaClientError> = None;
+
+        for url in self.build_uris("/v2/apps")? {
+            debug!("stream_applications url:{}", url);
+
+            let res = match self
+                .client
+                .get(url.clone())
+                .headers(self.headers_map())
+                .send()
+                .await
+            {
+                Ok(r) => r,
+                Err(e) => {
+                    last_err = Some(EurekaClientError::from(e));
+                    continue;
+                }
+            };
+
+            let status = res.status();
+            debug!("stream_applications: server response status={:?}", status);
+            match status {
+                s if s.is_success() => return ApplicationStream::new(res, options),
+                StatusCode::NOT_FOUND => continue,
+                s if s.is_server_error() => last_err = Some(EurekaClientError::InternalServerError),
+                s => {
+                    last_err = Some(EurekaClientError::GenericError(format!(
+                        "Unexpected status {} fetching applications",
+                        s
+                    )))
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or(EurekaClientError::NotFound))
+    }
+
+    async fn fetch_applications(
+        &self,
+        path: &str,
+    ) -> Result<(ApplicationsResponse, Url), EurekaClientError> {
         let uris = self.build_uris(path)?;
         let mut last_err: Option<EurekaClientError> = None;
 
@@ -469,6 +834,11 @@ impl EurekaClient {
                 debug!("received NotFound (404) from server");
                 continue;
             }
+            if !status.is_success() {
+                // a failing server is skipped like an unreachable one
+                last_err = Some(status_error(status));
+                continue;
+            }
 
             let body_bytes = res.bytes().await.map_err(EurekaClientError::from)?;
 
@@ -478,7 +848,7 @@ impl EurekaClient {
             })?;
 
             debug!("returning: {:?}", apps);
-            return Ok(apps);
+            return Ok((apps, url));
         }
 
         if let Some(e) = last_err {
@@ -488,14 +858,28 @@ impl EurekaClient {
         }
     }
 
-    fn build_uri(&self, path: &str) -> Result<Url, EurekaClientError> {
-        let url = format!("{}{}", self.eureka_cluster_url, path);
+    fn remember_lease_url(&self, url: Url) {
+        if let Ok(mut lease_urls) = self.lease_urls.lock() {
+            lease_urls.insert(url);
+        }
+    }
+
+    fn is_lease_url(&self, url: &Url) -> bool {
+        self.lease_urls
+            .lock()
+            .map(|lease_urls| lease_urls.contains(url))
+            .unwrap_or(false)
+    }
+
+    fn build_uri(&self, base: &str, path: &str) -> Result<Url, EurekaClientError> {
+        let url = format!("{}{}", base, path);
         Url::parse(&url).map_err(EurekaClientError::from)
     }
 
     /// Build a list of candidate URIs to try for a given path.
     /// Some Eureka distributions mount under /eureka, others serve at root. To be robust,
     /// we try both the configured base URL as-is and with a `/eureka` prefix when appropriate.
+    /// Every configured server is covered, in zone preference order.
     fn build_uris(&self, path: &str) -> Result<Vec<Url>, EurekaClientError> {
         let mut uris = Vec::new();
 
@@ -507,21 +891,23 @@ impl EurekaClient {
 
         let prefix = "/eureka";
 
-        for p in paths {
-            // direct
-            if let Ok(u) = self.build_uri(&p) {
-                if !uris.contains(&u) {
-                    uris.push(u);
-                }
-            }
-            // try with /eureka prefix if not already present in base
-            if !self.eureka_cluster_url.ends_with(prefix) {
-                let path_with_prefix = format!("{}{}", prefix, p);
-                if let Ok(u) = self.build_uri(&path_with_prefix) {
+        for base in &self.eureka_cluster_urls {
+            for p in &paths {
+                // direct
+                if let Ok(u) = self.build_uri(base, p) {
                     if !uris.contains(&u) {
                         uris.push(u);
                     }
                 }
+                // try with /eureka prefix if not already present in base
+                if !base.ends_with(prefix) {
+                    let path_with_prefix = format!("{}{}", prefix, p);
+                    if let Ok(u) = self.build_uri(base, &path_with_prefix) {
+                        if !uris.contains(&u) {
+                            uris.push(u);
+                        }
+                    }
+                }
             }
         }
 
@@ -546,3 +932,54 @@ impl EurekaClient {
         headers
     }
 }
+
+/// The error for a server answering with a status other than success or 404
+fn status_error(status: StatusCode) -> EurekaClientError {
+    match status {
+        StatusCode::BAD_REQUEST => EurekaClientError::BadRequest,
+        s if s.is_server_error() => EurekaClientError::InternalServerError,
+        s => EurekaClientError::GenericError(format!("Unexpected status {}", s)),
+    }
+}
+
+/// Escapes text for an XML element or attribute
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders metadata as `<metadata><key>value</key>...</metadata>`, the Java empty map
+/// marker when there is none, or `None` when a key is not a valid XML element name
+fn xml_metadata(metadata: &serde_json::Map<String, serde_json::Value>) -> Option<String> {
+    if metadata.is_empty() {
+        return Some("<metadata class=\"java.util.Collections$EmptyMap\"/>".to_owned());
+    }
+    let mut xml = String::from("<metadata>");
+    for (key, value) in metadata {
+        let valid_name = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            && !key.to_ascii_lowercase().starts_with("xml");
+        if !valid_name {
+            return None;
+        }
+        let text = match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        xml.push_str(&format!("<{0}>{1}</{0}>", key, xml_escape(&text)));
+    }
+    xml.push_str("</metadata>");
+    Some(xml)
+}
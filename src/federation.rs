@@ -0,0 +1,362 @@
+use crate::errors::EurekaClientError;
+use crate::load_balancer::instance_key;
+use crate::response::{intern, Application, Applications};
+use crate::EurekaClient;
+use futures::future::join_all;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time;
+
+/// How long a cluster gets to answer before the merged view is built without it
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What to do with an app registered in several clusters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictRule {
+    /// Serve the instances of every cluster as one application. An instance found in
+    /// several clusters by host and port is kept from the first of them.
+    #[default]
+    Merge,
+    /// Serve only the instances of the first cluster, in the order clusters were added
+    FirstCluster,
+    /// Keep each cluster's copy apart, renamed `<cluster>/<app>`
+    Namespace,
+}
+
+/// The outcome of the latest fetches from a cluster
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClusterHealth {
+    /// When the registry of the cluster was last fetched
+    pub last_success: Option<Instant>,
+    /// The number of fetches that failed since then
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+impl ClusterHealth {
+    /// Whether the latest fetch succeeded
+    pub fn is_healthy(&self) -> bool {
+        self.last_success.is_some() && self.consecutive_failures == 0
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClusterState {
+    health: ClusterHealth,
+    // the last registry fetched, tagged with the cluster name
+    applications: Option<Vec<Application>>,
+}
+
+struct Cluster {
+    name: Arc<str>,
+    client: Arc<EurekaClient>,
+    state: Mutex<ClusterState>,
+}
+
+/// A unified view over several independent Eureka clusters, e.g. one per environment or
+/// business unit.
+///
+/// Every fetch queries all the clusters concurrently and merges their registries into one
+/// [Applications](../response/struct.Applications.html), with each instance tagged with the
+/// name of the cluster it came from. A cluster that fails or does not answer within the fetch
+/// timeout contributes the last registry fetched from it, so one cluster outage neither
+/// stalls nor empties the merged view; its [health](#method.health) records the failures.
+pub struct FederatedClient {
+    clusters: Vec<Cluster>,
+    default_rule: ConflictRule,
+    rules: HashMap<String, ConflictRule>,
+    fetch_timeout: Duration,
+}
+
+impl FederatedClient {
+    /// Creates a client without any cluster, merging conflicting apps
+    pub fn new() -> FederatedClient {
+        FederatedClient {
+            clusters: Vec::new(),
+            default_rule: ConflictRule::default(),
+            rules: HashMap::new(),
+            fetch_timeout: DEFAULT_FETCH_TIMEOUT,
+        }
+    }
+
+    /// Adds a cluster. Clusters added first take precedence when resolving conflicts.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name instances of the cluster are tagged with
+    /// * `client` - The client of the cluster's Eureka servers
+    pub fn cluster(mut self, name: &str, client: Arc<EurekaClient>) -> FederatedClient {
+        self.clusters.push(Cluster {
+            name: intern(name),
+            client,
+            state: Mutex::new(ClusterState::default()),
+        });
+        self
+    }
+
+    /// The rule for apps registered in several clusters, unless overridden per app
+    pub fn conflict_rule(mut self, rule: ConflictRule) -> FederatedClient {
+        self.default_rule = rule;
+        self
+    }
+
+    /// The rule for one app registered in several clusters, ignoring case
+    pub fn app_conflict_rule(mut self, app: &str, rule: ConflictRule) -> FederatedClient {
+        self.rules.insert(app.to_ascii_uppercase(), rule);
+        self
+    }
+
+    pub fn fetch_timeout(mut self, timeout: Duration) -> FederatedClient {
+        self.fetch_timeout = timeout;
+        self
+    }
+
+    /// The names of the clusters, in precedence order
+    pub fn clusters(&self) -> impl Iterator<Item = &str> {
+        self.clusters.iter().map(|c| &*c.name)
+    }
+
+    /// The health of a cluster, or `None` when there is no cluster by that name
+    pub fn health(&self, cluster: &str) -> Option<ClusterHealth> {
+        let cluster = self.clusters.iter().find(|c| &*c.name == cluster)?;
+        let state = cluster.state.lock().ok()?;
+        Some(state.health.clone())
+    }
+
+    /// Fetches the registries of every cluster concurrently and merges them.
+    ///
+    /// Fails only when no registry was ever fetched from any cluster, with the error of
+    /// the first cluster that failed.
+    pub async fn fetch(&self) -> Result<Applications, EurekaClientError> {
+        let results = join_all(self.clusters.iter().map(|cluster| async move {
+            let result = time::timeout(self.fetch_timeout, cluster.client.get_applications())
+                .await
+                .unwrap_or_else(|_| {
+                    Err(EurekaClientError::GenericError(format!(
+                        "timed out fetching the registry of cluster {}",
+                        cluster.name
+                    )))
+                });
+            (cluster, result)
+        }))
+        .await;
+
+        let mut first_err = None;
+        for (cluster, result) in results {
+            let Ok(mut state) = cluster.state.lock() else {
+                continue;
+            };
+            match result {
+                Ok(res) => {
+                    let mut applications = res.applications.applications;
+                    for instance in applications.iter_mut().flat_map(|a| a.instance.iter_mut()) {
+                        instance.cluster = Some(cluster.name.clone());
+                    }
+                    state.applications = Some(applications);
+                    state.health.last_success = Some(Instant::now());
+                    state.health.consecutive_failures = 0;
+                    state.health.last_error = None;
+                }
+                Err(e) => {
+                    warn!(
+                        "failed to fetch the registry of cluster {}: {}",
+                        cluster.name, e
+                    );
+                    state.health.consecutive_failures += 1;
+                    state.health.last_error = Some(e.to_string());
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_err {
+            Some(e) if !self.has_registry() => Err(e),
+            _ => Ok(self.merge()),
+        }
+    }
+
+    fn has_registry(&self) -> bool {
+        self.clusters.iter().any(|c| {
+            c.state
+                .lock()
+                .map(|s| s.applications.is_some())
+                .unwrap_or(false)
+        })
+    }
+
+    fn rule(&self, app: &str) -> ConflictRule {
+        self.rules.get(app).copied().unwrap_or(self.default_rule)
+    }
+
+    // merges the last registry of every cluster, in precedence order
+    fn merge(&self) -> Applications {
+        let registries = self
+            .clusters
+            .iter()
+            .filter_map(|c| {
+                let state = c.state.lock().ok()?;
+                Some((c.name.clone(), state.applications.clone()?))
+            })
+            .collect::<Vec<_>>();
+
+        let mut clusters_per_app: HashMap<String, usize> = HashMap::new();
+        for (_, applications) in &registries {
+            for application in applications {
+                *clusters_per_app
+                    .entry(application.name.to_ascii_uppercase())
+                    .or_default() += 1;
+            }
+        }
+
+        let mut merged: Vec<Application> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (cluster, applications) in registries {
+            for mut application in applications {
+                let key = application.name.to_ascii_uppercase();
+                let rule = self.rule(&key);
+                if clusters_per_app[&key] > 1 && rule == ConflictRule::Namespace {
+                    application.name = intern(&format!("{}/{}", cluster, application.name));
+                    merged.push(application);
+                    continue;
+                }
+                match positions.get(&key) {
+                    None => {
+                        positions.insert(key, merged.len());
+                        merged.push(application);
+                    }
+                    Some(&p) if rule == ConflictRule::Merge => {
+                        let instances = &mut merged[p].instance;
+                        let known = instances.iter().map(instance_key).collect::<HashSet<_>>();
+                        instances.extend(
+                            application
+                                .instance
+                                .into_iter()
+                                .filter(|i| !known.contains(&instance_key(i))),
+                        );
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        // the merged view has no version of its own
+        let mut applications = Applications {
+            versions_delta: 0,
+            apps_hashcode: String::new(),
+            applications: merged,
+        };
+        applications.apps_hashcode = applications.reconcile_hashcode();
+        applications
+    }
+}
+
+impl Default for FederatedClient {
+    fn default() -> Self {
+        FederatedClient::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::build_test_instance;
+
+    fn client() -> Arc<EurekaClient> {
+        Arc::new(EurekaClient::new("TEST", "http://localhost:1").expect("test unwrap"))
+    }
+
+    fn application(name: &str, ports: &[u16]) -> Application {
+        Application {
+            name: name.into(),
+            instance: ports
+                .iter()
+                .map(|port| {
+                    let mut instance = build_test_instance();
+                    instance.port = Some(*port);
+                    instance
+                })
+                .collect(),
+        }
+    }
+
+    fn federation(rule: ConflictRule) -> FederatedClient {
+        let federation = FederatedClient::new()
+            .cluster("prod", client())
+            .cluster("staging", client())
+            .conflict_rule(rule);
+        for (cluster, applications) in federation.clusters.iter().zip([
+            vec![
+                application("BAR", &[8080, 8081]),
+                application("BAZ", &[9090]),
+            ],
+            vec![application("bar", &[8081, 8082])],
+        ]) {
+            cluster.state.lock().expect("test unwrap").applications = Some(applications);
+        }
+        federation
+    }
+
+    fn summary(applications: &Applications) -> Vec<(String, Vec<Option<u16>>)> {
+        applications
+            .applications
+            .iter()
+            .map(|a| {
+                (
+                    a.name.to_string(),
+                    a.instance.iter().map(|i| i.port).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_conflict_rules() {
+        assert_eq!(
+            vec![
+                ("BAR".to_owned(), vec![Some(8080), Some(8081), Some(8082)]),
+                ("BAZ".to_owned(), vec![Some(9090)]),
+            ],
+            summary(&federation(ConflictRule::Merge).merge())
+        );
+        assert_eq!(
+            vec![
+                ("BAR".to_owned(), vec![Some(8080), Some(8081)]),
+                ("BAZ".to_owned(), vec![Some(9090)]),
+            ],
+            summary(&federation(ConflictRule::FirstCluster).merge())
+        );
+        assert_eq!(
+            vec![
+                ("prod/BAR".to_owned(), vec![Some(8080), Some(8081)]),
+                ("BAZ".to_owned(), vec![Some(9090)]),
+                ("staging/bar".to_owned(), vec![Some(8081), Some(8082)]),
+            ],
+            summary(&federation(ConflictRule::Namespace).merge())
+        );
+
+        let federation = federation(ConflictRule::Namespace)
+            .app_conflict_rule("bar", ConflictRule::FirstCluster);
+        assert_eq!(
+            vec![
+                ("BAR".to_owned(), vec![Some(8080), Some(8081)]),
+                ("BAZ".to_owned(), vec![Some(9090)]),
+            ],
+            summary(&federation.merge())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_clusters() {
+        let federation = FederatedClient::new()
+            .cluster("prod", client())
+            .fetch_timeout(Duration::from_secs(1));
+        assert!(federation.fetch().await.is_err());
+
+        let health = federation.health("prod").expect("test unwrap");
+        assert!(!health.is_healthy());
+        assert_eq!(1, health.consecutive_failures);
+        assert!(health.last_error.is_some());
+        assert_eq!(None, federation.health("staging"));
+    }
+}
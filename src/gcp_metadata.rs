@@ -0,0 +1,141 @@
+use crate::errors::EurekaClientError;
+use crate::request::{DataCenterInfo, DcName, Instance};
+use futures::future::try_join_all;
+use reqwest::{Client, StatusCode};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+/// The address of the GCP metadata server
+pub const DEFAULT_METADATA_URL: &str = "http://metadata.google.internal";
+/// The data center name used for instances populated from GCP metadata
+pub const GCP_DC_NAME: &str = "GCP";
+
+pub const ZONE: &str = "zone";
+pub const REGION: &str = "region";
+pub const PROJECT_ID: &str = "project-id";
+pub const INSTANCE_ID: &str = "instance-id";
+pub const HOSTNAME: &str = "hostname";
+pub const LOCAL_IPV4: &str = "local-ipv4";
+
+const COMPUTE_METADATA_PATH: &str = "/computeMetadata/v1/";
+const FLAVOR_HEADER: &str = "Metadata-Flavor";
+const FLAVOR_VALUE: &str = "Google";
+
+// (metadata server path, key in the custom metadata map)
+const KEYS: &[(&str, &str)] = &[
+    ("instance/zone", ZONE),
+    ("project/project-id", PROJECT_ID),
+    ("instance/id", INSTANCE_ID),
+    ("instance/hostname", HOSTNAME),
+    ("instance/network-interfaces/0/ip", LOCAL_IPV4),
+];
+
+/// Reads zone, project and instance details from a GCP-style metadata server
+/// and stores them as custom `DataCenterInfo` metadata.
+pub struct GcpMetadataProvider {
+    client: Client,
+    base_url: String,
+}
+
+impl GcpMetadataProvider {
+    /// Creates a provider for the standard metadata server address
+    pub fn new() -> Result<GcpMetadataProvider, EurekaClientError> {
+        GcpMetadataProvider::with_base_url(DEFAULT_METADATA_URL)
+    }
+
+    /// Creates a provider for the given base url, e.g. a local stub during tests
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The metadata server url, without the `/computeMetadata/...` path
+    pub fn with_base_url(base_url: &str) -> Result<GcpMetadataProvider, EurekaClientError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(2))
+            .build()
+            .map_err(EurekaClientError::from)?;
+        Ok(GcpMetadataProvider {
+            client,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        })
+    }
+
+    /// Fetches the metadata, leaving out any key the server does not publish.
+    /// The region is derived from the zone.
+    pub async fn fetch(&self) -> Result<Map<String, Value>, EurekaClientError> {
+        let values = try_join_all(KEYS.iter().map(|(path, _)| self.get(path))).await?;
+
+        let mut metadata = Map::new();
+        for ((_, key), value) in KEYS.iter().zip(values) {
+            if let Some(v) = value {
+                metadata.insert(key.to_string(), Value::String(v));
+            }
+        }
+
+        // the server returns the zone as projects/{number}/zones/{zone}
+        if let Some(Value::String(zone)) = metadata.get_mut(ZONE) {
+            if let Some(short) = zone.rsplit('/').next().map(str::to_owned) {
+                *zone = short;
+            }
+        }
+        let region = metadata
+            .get(ZONE)
+            .and_then(Value::as_str)
+            .and_then(|z| z.rsplit_once('-'))
+            .map(|(region, _)| region.to_owned());
+        if let Some(region) = region {
+            metadata.insert(REGION.to_owned(), Value::String(region));
+        }
+        Ok(metadata)
+    }
+
+    /// Fetches the metadata and stores it as a `GCP` `DataCenterInfo` on the instance
+    ///
+    /// # Arguments
+    ///
+    /// * `instance` - The instance to update
+    /// * `set_host_and_ip` - Also replace the host name and ip address with the
+    ///   instance's hostname and primary network interface address
+    pub async fn populate(
+        &self,
+        instance: &mut Instance,
+        set_host_and_ip: bool,
+    ) -> Result<(), EurekaClientError> {
+        let metadata = self.fetch().await?;
+        if set_host_and_ip {
+            if let Some(host) = metadata.get(HOSTNAME).and_then(Value::as_str) {
+                instance.host_name = host.to_owned();
+            }
+            if let Some(ip) = metadata.get(LOCAL_IPV4).and_then(Value::as_str) {
+                instance.ip_addr = ip.to_owned();
+            }
+        }
+        instance.data_center_info = DataCenterInfo {
+            name: DcName::Custom(GCP_DC_NAME.to_owned()),
+            metadata: None,
+            custom_metadata: Some(metadata),
+        };
+        Ok(())
+    }
+
+    async fn get(&self, path: &str) -> Result<Option<String>, EurekaClientError> {
+        let url = format!("{}{}{}", self.base_url, COMPUTE_METADATA_PATH, path);
+        let res = self
+            .client
+            .get(url)
+            .header(FLAVOR_HEADER, FLAVOR_VALUE)
+            .send()
+            .await?;
+
+        match res.status() {
+            s if s.is_success() => Ok(Some(res.text().await?.trim().to_owned())),
+            StatusCode::NOT_FOUND => {
+                debug!("metadata path {} is not available", path);
+                Ok(None)
+            }
+            s => Err(EurekaClientError::GenericError(format!(
+                "Metadata server returned {} for {}",
+                s, path
+            ))),
+        }
+    }
+}
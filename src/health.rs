@@ -0,0 +1,234 @@
+use crate::request::{RegisterRequest, Status};
+use crate::EurekaClient;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+
+/// How often health is checked by default, matching Eureka's lease renewal interval
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// How many consecutive checks must agree before a new status is pushed by default
+pub const DEFAULT_DEBOUNCE: usize = 2;
+
+/// Reports the health of this instance as a Eureka status
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    async fn check(&self) -> Status;
+}
+
+#[async_trait]
+impl<F, Fut> HealthCheck for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Status> + Send,
+{
+    async fn check(&self) -> Status {
+        self().await
+    }
+}
+
+/// Aggregates several checks into one status, reporting the worst of them:
+/// `DOWN`, then `OUT_OF_SERVICE`, `STARTING`, `UNKNOWN` and finally `UP`
+#[derive(Default)]
+pub struct CompositeHealthCheck {
+    checks: Vec<Box<dyn HealthCheck>>,
+}
+
+impl CompositeHealthCheck {
+    pub fn new() -> CompositeHealthCheck {
+        CompositeHealthCheck::default()
+    }
+
+    pub fn with_check<C: HealthCheck + 'static>(mut self, check: C) -> CompositeHealthCheck {
+        self.checks.push(Box::new(check));
+        self
+    }
+}
+
+#[async_trait]
+impl HealthCheck for CompositeHealthCheck {
+    async fn check(&self) -> Status {
+        futures::future::join_all(self.checks.iter().map(|c| c.check()))
+            .await
+            .into_iter()
+            .max_by_key(severity)
+            .unwrap_or(Status::Up)
+    }
+}
+
+fn severity(status: &Status) -> u8 {
+    match status {
+        Status::Up => 0,
+        Status::Unknown => 1,
+        Status::Starting => 2,
+        Status::OutOfService => 3,
+        Status::Down => 4,
+    }
+}
+
+/// Decides when a run of check results is stable enough to report
+#[derive(Debug)]
+struct Debouncer {
+    reported: Status,
+    candidate: Status,
+    seen: usize,
+    threshold: usize,
+}
+
+impl Debouncer {
+    fn new(reported: Status, threshold: usize) -> Debouncer {
+        Debouncer {
+            candidate: reported.clone(),
+            reported,
+            seen: 0,
+            threshold: threshold.max(1),
+        }
+    }
+
+    /// Records a check result, returning the status to push when it should change
+    fn observe(&mut self, status: Status) -> Option<Status> {
+        if status == self.candidate {
+            self.seen += 1;
+        } else {
+            self.candidate = status;
+            self.seen = 1;
+        }
+        if self.candidate != self.reported && self.seen >= self.threshold {
+            Some(self.candidate.clone())
+        } else {
+            None
+        }
+    }
+
+    fn reported(&mut self, status: Status) {
+        self.reported = status;
+    }
+}
+
+/// Polls a [HealthCheck](trait.HealthCheck.html) and pushes the status of the instance
+/// through Eureka's status endpoint whenever it changes. A status override set remotely,
+/// e.g. `OUT_OF_SERVICE`, is left alone unless the instance reports itself `DOWN` or
+/// `STARTING`.
+pub struct HealthReporter {
+    client: Arc<EurekaClient>,
+    registration: RegisterRequest,
+    check: Box<dyn HealthCheck>,
+    interval: Duration,
+    debounce: usize,
+}
+
+impl HealthReporter {
+    /// Creates a new reporter
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client used to push status changes
+    /// * `registration` - The registration of the instance, whose status is the last reported one
+    /// * `check` - The health check to poll
+    pub fn new<C: HealthCheck + 'static>(
+        client: Arc<EurekaClient>,
+        registration: RegisterRequest,
+        check: C,
+    ) -> HealthReporter {
+        HealthReporter {
+            client,
+            registration,
+            check: Box::new(check),
+            interval: DEFAULT_CHECK_INTERVAL,
+            debounce: DEFAULT_DEBOUNCE,
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> HealthReporter {
+        self.interval = interval;
+        self
+    }
+
+    /// The number of consecutive checks that must agree before a new status is pushed
+    pub fn debounce(mut self, debounce: usize) -> HealthReporter {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Runs the reporter on the tokio runtime until the returned handle is aborted
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    /// Checks health every interval, forever
+    pub async fn run(mut self) {
+        let mut interval = time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut debouncer =
+            Debouncer::new(self.registration.instance.status.clone(), self.debounce);
+        loop {
+            interval.tick().await;
+            let status = match debouncer.observe(self.check.check().await) {
+                Some(status) => status,
+                None => continue,
+            };
+
+            let app = self.registration.instance.app.clone();
+            let instance_id = self.registration.instance.host_name.clone();
+            if let Some(status_override) = self
+                .client
+                .conflicting_override(&app, &instance_id, &status)
+                .await
+                // the override left by the last status pushed here is not a conflict
+                .filter(|o| String::from(o) != String::from(&self.registration.instance.status))
+            {
+                // pushed once the override is lifted
+                info!(
+                    "not reporting status {:?} for {} while overridden as {:?}",
+                    status, app, status_override
+                );
+                continue;
+            }
+
+            match self.client.update_status(&app, &instance_id, &status).await {
+                Ok(()) => {
+                    info!("reported status {:?} for {}", status, app);
+                    self.registration.instance.status = status.clone();
+                    debouncer.reported(status);
+                }
+                // the change is pushed again at the next check
+                Err(e) => warn!("failed to report status {:?} for {}: {}", status, app, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debouncer() {
+        let mut debouncer = Debouncer::new(Status::Up, 2);
+        assert_eq!(None, debouncer.observe(Status::Up));
+        assert_eq!(None, debouncer.observe(Status::Down));
+        // flapping back resets the count
+        assert_eq!(None, debouncer.observe(Status::Up));
+        assert_eq!(None, debouncer.observe(Status::Down));
+        assert_eq!(Some(Status::Down), debouncer.observe(Status::Down));
+        // repeated until reported
+        assert_eq!(Some(Status::Down), debouncer.observe(Status::Down));
+        debouncer.reported(Status::Down);
+        assert_eq!(None, debouncer.observe(Status::Down));
+    }
+
+    #[tokio::test]
+    async fn test_composite_reports_worst_status() {
+        let composite = CompositeHealthCheck::new()
+            .with_check(|| async { Status::Up })
+            .with_check(|| async { Status::OutOfService })
+            .with_check(|| async { Status::Starting });
+        assert_eq!(Status::OutOfService, composite.check().await);
+
+        let composite = composite.with_check(|| async { Status::Down });
+        assert_eq!(Status::Down, composite.check().await);
+        assert_eq!(Status::Up, CompositeHealthCheck::new().check().await);
+    }
+}
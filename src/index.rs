@@ -0,0 +1,383 @@
+use crate::region::prefer_local;
+use crate::response::{Application, Applications, Instance, Status};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// (application, instance) positions in the snapshot
+type Positions = Vec<(usize, usize)>;
+
+/// An indexed, read-only view of a registry snapshot.
+///
+/// App names, VIPs and secure VIPs are matched case-insensitively as Eureka normalizes
+/// them, host names and IP addresses share one index, and instances are indexed by their
+/// effective status. The instances of an app fetched from several regions, as tagged by
+/// [RegionConfig::tag](../region/struct.RegionConfig.html#method.tag), are served from the
+/// local region first. Feeding a fresh snapshot to [rebuild](#method.rebuild) reuses the
+/// allocated indexes.
+#[derive(Debug, Default)]
+pub struct RegistryIndex {
+    versions_delta: i16,
+    apps_hashcode: String,
+    applications: Vec<Application>,
+    by_app: HashMap<String, usize>,
+    by_vip: HashMap<String, Positions>,
+    by_secure_vip: HashMap<String, Positions>,
+    by_id: HashMap<String, Positions>,
+    by_host: HashMap<String, Positions>,
+    by_zone: HashMap<String, Positions>,
+    by_status: HashMap<String, Positions>,
+}
+
+impl RegistryIndex {
+    /// Indexes a registry snapshot
+    pub fn new(applications: Applications) -> RegistryIndex {
+        let mut index = RegistryIndex::default();
+        index.rebuild(applications);
+        index
+    }
+
+    /// Replaces the indexed snapshot with a fresh one
+    pub fn rebuild(&mut self, applications: Applications) {
+        self.versions_delta = applications.versions_delta;
+        self.apps_hashcode = applications.apps_hashcode;
+        self.applications = applications.applications;
+        self.by_app.clear();
+        for map in [
+            &mut self.by_vip,
+            &mut self.by_secure_vip,
+            &mut self.by_id,
+            &mut self.by_host,
+            &mut self.by_zone,
+            &mut self.by_status,
+        ] {
+            map.values_mut().for_each(Vec::clear);
+        }
+
+        for (a, application) in self.applications.iter().enumerate() {
+            self.by_app.insert(normalize(&application.name), a);
+            for (i, instance) in application.instance.iter().enumerate() {
+                let position = (a, i);
+                push(&mut self.by_id, instance.id().to_owned(), position);
+                push(&mut self.by_vip, normalize(&instance.vip_address), position);
+                push(
+                    &mut self.by_secure_vip,
+                    normalize(&instance.secure_vip_address),
+                    position,
+                );
+                let host = instance.host_name.to_ascii_lowercase();
+                let ip = instance.ip_addr.to_ascii_lowercase();
+                if ip != host {
+                    push(&mut self.by_host, ip, position);
+                }
+                push(&mut self.by_host, host, position);
+                if let Some(zone) = instance.zone() {
+                    push(&mut self.by_zone, zone.to_owned(), position);
+                }
+                push(
+                    &mut self.by_status,
+                    String::from(instance.effective_status()),
+                    position,
+                );
+            }
+        }
+
+        // drop keys that are gone from the fresh snapshot
+        for map in [
+            &mut self.by_vip,
+            &mut self.by_secure_vip,
+            &mut self.by_id,
+            &mut self.by_host,
+            &mut self.by_zone,
+            &mut self.by_status,
+        ] {
+            map.retain(|_, positions| !positions.is_empty());
+        }
+    }
+
+    pub fn versions_delta(&self) -> i16 {
+        self.versions_delta
+    }
+
+    pub fn apps_hashcode(&self) -> &str {
+        &self.apps_hashcode
+    }
+
+    /// The number of indexed instances
+    pub fn len(&self) -> usize {
+        self.applications.iter().map(|a| a.instance.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.applications.iter().all(|a| a.instance.is_empty())
+    }
+
+    pub fn applications(&self) -> &[Application] {
+        &self.applications
+    }
+
+    /// Looks up an application by name, ignoring case
+    pub fn application(&self, name: &str) -> Option<&Application> {
+        self.by_app
+            .get(&normalize(name))
+            .map(|a| &self.applications[*a])
+    }
+
+    /// The instances of an application, ignoring case. When it was fetched from several
+    /// regions, those of the local one until none of them is up, see
+    /// [prefer_local](../region/fn.prefer_local.html).
+    pub fn app(&self, name: &str) -> impl Iterator<Item = &Instance> + '_ {
+        self.application(name)
+            .map(prefer_local)
+            .unwrap_or_default()
+            .into_iter()
+    }
+
+    /// The instances of an application, ignoring case, in one region
+    pub fn app_in_region<'a>(
+        &'a self,
+        name: &str,
+        region: &'a str,
+    ) -> impl Iterator<Item = &'a Instance> + 'a {
+        self.application(name)
+            .into_iter()
+            .flat_map(|a| a.instance.iter())
+            .filter(move |i| i.region.as_deref() == Some(region))
+    }
+
+    /// The instances registered under a VIP address, ignoring case
+    pub fn vip(&self, vip: &str) -> impl Iterator<Item = &Instance> + '_ {
+        self.lookup(&self.by_vip, &normalize(vip))
+    }
+
+    /// The instances registered under a secure VIP address, ignoring case
+    pub fn secure_vip(&self, vip: &str) -> impl Iterator<Item = &Instance> + '_ {
+        self.lookup(&self.by_secure_vip, &normalize(vip))
+    }
+
+    /// Looks up an instance by id, which is the host name unless one was set. When
+    /// instances of several apps share the id, the first one is returned, see
+    /// [instances](#method.instances).
+    pub fn instance(&self, id: &str) -> Option<&Instance> {
+        self.instances(id).next()
+    }
+
+    /// The instances with the id, e.g. those of every app running on a host when no
+    /// instance id was set
+    pub fn instances(&self, id: &str) -> impl Iterator<Item = &Instance> + '_ {
+        self.lookup(&self.by_id, id)
+    }
+
+    /// The instances with the host name, ignoring case, or the IP address
+    pub fn host(&self, host_or_ip: &str) -> impl Iterator<Item = &Instance> + '_ {
+        self.lookup(&self.by_host, &host_or_ip.to_ascii_lowercase())
+    }
+
+    /// The instances in an availability zone
+    pub fn zone(&self, zone: &str) -> impl Iterator<Item = &Instance> + '_ {
+        self.lookup(&self.by_zone, zone)
+    }
+
+    /// The instances whose [effective status](../response/struct.Instance.html#method.effective_status)
+    /// is `status`
+    pub fn status(&self, status: &Status) -> impl Iterator<Item = &Instance> + '_ {
+        self.lookup(&self.by_status, &String::from(status))
+    }
+
+    /// Every instance matching the predicate
+    pub fn query<'a, P>(&'a self, predicate: P) -> impl Iterator<Item = &'a Instance> + 'a
+    where
+        P: Fn(&Instance) -> bool + 'a,
+    {
+        self.applications
+            .iter()
+            .flat_map(|a| a.instance.iter())
+            .filter(move |i| predicate(i))
+    }
+
+    /// Every instance whose metadata has the key set to the string value
+    pub fn with_metadata<'a>(
+        &'a self,
+        key: &'a str,
+        value: &'a str,
+    ) -> impl Iterator<Item = &'a Instance> + 'a {
+        self.query(move |i| i.metadata.get(key).and_then(Value::as_str) == Some(value))
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        map: &'a HashMap<String, Positions>,
+        key: &str,
+    ) -> impl Iterator<Item = &'a Instance> + 'a {
+        map.get(key)
+            .into_iter()
+            .flatten()
+            .map(move |p| self.get(*p))
+    }
+
+    fn get(&self, (a, i): (usize, usize)) -> &Instance {
+        &self.applications[a].instance[i]
+    }
+}
+
+impl From<Applications> for RegistryIndex {
+    fn from(applications: Applications) -> Self {
+        RegistryIndex::new(applications)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.to_ascii_uppercase()
+}
+
+fn push(map: &mut HashMap<String, Positions>, key: String, position: (usize, usize)) {
+    map.entry(key).or_default().push(position);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::build_test_instance;
+
+    fn build_test_applications() -> Applications {
+        let mut foo = build_test_instance();
+        foo.instance_id = Some("foo-1".to_owned());
+        let mut bar = build_test_instance();
+        bar.host_name = "Bar".to_owned();
+        bar.ip_addr = "3.128.2.13".to_owned();
+        bar.status = Status::Down;
+        bar.metadata.insert("version".to_owned(), Value::from("2"));
+        let mut baz = build_test_instance();
+        baz.app = "Baz".into();
+        baz.host_name = "Baz".to_owned();
+        baz.vip_address = "baz".into();
+        baz.overriddenstatus = Some(Status::OutOfService);
+
+        Applications {
+            versions_delta: 1,
+            apps_hashcode: "UP_1_".to_owned(),
+            applications: vec![
+                Application {
+                    name: "BAR".into(),
+                    instance: vec![foo, bar],
+                },
+                Application {
+                    name: "BAZ".into(),
+                    instance: vec![baz],
+                },
+            ],
+        }
+    }
+
+    fn hosts<'a>(instances: impl Iterator<Item = &'a Instance>) -> Vec<&'a str> {
+        let mut hosts = instances.map(|i| i.host_name.as_str()).collect::<Vec<_>>();
+        hosts.sort();
+        hosts
+    }
+
+    #[test]
+    fn test_lookups() {
+        let index = RegistryIndex::new(build_test_applications());
+        assert_eq!(3, index.len());
+        assert_eq!(vec!["Bar", "Foo"], hosts(index.app("bar")));
+        assert_eq!("BAZ", &*index.application("Baz").expect("test unwrap").name);
+        assert_eq!(vec!["Bar", "Foo"], hosts(index.vip("127.0.0.1")));
+        assert_eq!(vec!["Baz"], hosts(index.vip("BAZ")));
+        assert_eq!(3, index.secure_vip("127.0.0.2").count());
+        assert_eq!(
+            "Foo",
+            index.instance("foo-1").expect("test unwrap").host_name
+        );
+        assert_eq!("Bar", index.instance("Bar").expect("test unwrap").host_name);
+        assert!(index.instance("Foo").is_none());
+        assert_eq!(vec!["Bar"], hosts(index.host("bar")));
+        assert_eq!(vec!["Bar"], hosts(index.host("3.128.2.13")));
+        assert_eq!(3, index.zone("US_East1a").count());
+        assert_eq!(vec!["Foo"], hosts(index.status(&Status::Up)));
+        assert_eq!(vec!["Bar"], hosts(index.status(&Status::Down)));
+        assert_eq!(vec!["Baz"], hosts(index.status(&Status::OutOfService)));
+        assert_eq!(vec!["Bar"], hosts(index.with_metadata("version", "2")));
+        assert_eq!(
+            vec!["Baz", "Foo"],
+            hosts(index.query(|i| i.status == Status::Up))
+        );
+        assert_eq!(0, index.app("missing").count());
+    }
+
+    #[test]
+    fn test_rebuild() {
+        let mut index = RegistryIndex::new(build_test_applications());
+        let mut applications = build_test_applications();
+        applications.applications.truncate(1);
+        applications.applications[0].instance[1].status = Status::Up;
+        index.rebuild(applications);
+
+        assert_eq!(2, index.len());
+        assert!(index.application("baz").is_none());
+        assert_eq!(0, index.vip("baz").count());
+        assert!(!index.by_vip.contains_key("BAZ"));
+        assert_eq!(0, index.status(&Status::Down).count());
+        assert_eq!(vec!["Bar", "Foo"], hosts(index.status(&Status::Up)));
+    }
+
+    #[test]
+    fn test_prefers_local_region() {
+        let mut applications = build_test_applications();
+        let mut remote = build_test_instance();
+        remote.host_name = "Remote".to_owned();
+        remote.region = Some("us-west-2".into());
+        for instance in applications.applications[0].instance.iter_mut() {
+            instance.region = Some("us-east-1".into());
+        }
+        applications.applications[0].instance.push(remote);
+
+        let index = RegistryIndex::new(applications.clone());
+        assert_eq!(vec!["Bar", "Foo"], hosts(index.app("bar")));
+        assert_eq!(
+            vec!["Remote"],
+            hosts(index.app_in_region("bar", "us-west-2"))
+        );
+        assert_eq!(
+            3,
+            index
+                .application("bar")
+                .expect("test unwrap")
+                .instance
+                .len()
+        );
+
+        for instance in applications.applications[0].instance.iter_mut().take(2) {
+            instance.status = Status::Down;
+        }
+        let index = RegistryIndex::new(applications);
+        assert_eq!(vec!["Remote"], hosts(index.app("bar")));
+        assert_eq!(4, index.len());
+    }
+
+    #[test]
+    fn test_zone_from_metadata() {
+        let mut applications = build_test_applications();
+        let baz = &mut applications.applications[1].instance[0];
+        baz.data_center_info.metadata = None;
+        baz.metadata
+            .insert("zone".to_owned(), Value::from("us-east-1c"));
+
+        let index = RegistryIndex::new(applications);
+        assert_eq!(vec!["Baz"], hosts(index.zone("us-east-1c")));
+        assert_eq!(2, index.zone("US_East1a").count());
+    }
+
+    #[test]
+    fn test_shared_instance_id() {
+        let mut applications = build_test_applications();
+        let mut other = build_test_instance();
+        other.app = "Baz".into();
+        other.host_name = "Bar".to_owned();
+        applications.applications[1].instance.push(other);
+
+        let index = RegistryIndex::new(applications);
+        assert_eq!(4, index.len());
+        let apps = index.instances("Bar").map(|i| &*i.app).collect::<Vec<_>>();
+        assert_eq!(vec!["Bar", "Baz"], apps);
+        assert_eq!("Bar", &*index.instance("Bar").expect("test unwrap").app);
+    }
+}
@@ -1,9 +1,32 @@
 #[macro_use]
 extern crate log;
 
+pub mod diff;
+#[cfg(feature = "discover")]
+pub mod discover;
+pub mod ec2_metadata;
 pub mod errors;
 pub mod eureka_client;
+pub mod federation;
+pub mod gcp_metadata;
+pub mod health;
+pub mod index;
+pub mod load_balancer;
+#[cfg(feature = "middleware")]
+pub mod middleware;
+pub mod outlier;
+pub mod persistence;
+pub mod readiness;
+pub mod region;
+pub mod registry;
 pub mod request;
 pub mod response;
+pub mod service;
+pub mod shutdown;
+pub mod snapshot;
+pub mod static_registry;
+pub mod streaming;
+pub mod watch;
+pub mod zone;
 
 pub use eureka_client::EurekaClient;
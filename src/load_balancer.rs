@@ -0,0 +1,340 @@
+use crate::response::{Application, Instance, Status};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The metadata key read by [WeightedLoadBalancer](struct.WeightedLoadBalancer.html) by default
+pub const DEFAULT_WEIGHT_KEY: &str = "weight";
+
+/// Picks one instance from those discovered for an application
+pub trait LoadBalancer: Send + Sync {
+    /// Chooses one of the candidates, which are all `UP`
+    fn choose<'a>(&self, candidates: &[&'a Instance]) -> Option<&'a Instance>;
+
+    /// Chooses an instance of the application whose effective status is `UP`
+    fn select<'a>(&self, application: &'a Application) -> Option<&'a Instance> {
+        let candidates = application
+            .instance
+            .iter()
+            .filter(|i| is_up(i))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return None;
+        }
+        self.choose(&candidates)
+    }
+
+    /// Chooses an `UP` instance of the application and returns its base url,
+    /// see [base_url](fn.base_url.html)
+    fn select_url(&self, application: &Application, secure: bool) -> Option<String> {
+        self.select(application).and_then(|i| base_url(i, secure))
+    }
+}
+
+/// Builds `http://{host}:{port}` for an instance, or `https://{host}:{secure_port}`
+/// when `secure` is requested or the instance has no plain port
+pub fn base_url(instance: &Instance, secure: bool) -> Option<String> {
+    let host = url_host(&instance.host_name);
+    match (secure, instance.port, instance.secure_port) {
+        (false, Some(p), _) => Some(format!("http://{}:{}", host, p)),
+        (_, _, Some(p)) => Some(format!("https://{}:{}", host, p)),
+        _ => None,
+    }
+}
+
+/// The host as written in a url, with IPv6 addresses in brackets
+pub(crate) fn url_host(host: &str) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]", host)
+    } else {
+        host.to_owned()
+    }
+}
+
+/// The key used to track an instance across registry refreshes
+pub fn instance_key(instance: &Instance) -> String {
+    format!(
+        "{}:{}",
+        instance.host_name,
+        instance.port.or(instance.secure_port).unwrap_or_default()
+    )
+}
+
+pub(crate) fn is_up(instance: &Instance) -> bool {
+    instance.effective_status() == Status::Up
+}
+
+/// Cycles through the candidates in order
+#[derive(Debug, Default)]
+pub struct RoundRobinLoadBalancer {
+    next: AtomicUsize,
+}
+
+impl RoundRobinLoadBalancer {
+    pub fn new() -> RoundRobinLoadBalancer {
+        RoundRobinLoadBalancer::default()
+    }
+}
+
+impl LoadBalancer for RoundRobinLoadBalancer {
+    fn choose<'a>(&self, candidates: &[&'a Instance]) -> Option<&'a Instance> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        Some(candidates[n % candidates.len()])
+    }
+}
+
+/// Picks a candidate uniformly at random
+#[derive(Debug, Default)]
+pub struct RandomLoadBalancer;
+
+impl RandomLoadBalancer {
+    pub fn new() -> RandomLoadBalancer {
+        RandomLoadBalancer
+    }
+}
+
+impl LoadBalancer for RandomLoadBalancer {
+    fn choose<'a>(&self, candidates: &[&'a Instance]) -> Option<&'a Instance> {
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(candidates[fastrand::usize(..candidates.len())])
+    }
+}
+
+/// Picks a candidate at random, proportionally to a weight read from its metadata.
+/// Instances without a parseable weight count as 1, and a weight of 0 takes the
+/// instance out of rotation.
+#[derive(Debug)]
+pub struct WeightedLoadBalancer {
+    weight_key: String,
+}
+
+impl WeightedLoadBalancer {
+    /// Reads the weight from the `weight` metadata key
+    pub fn new() -> WeightedLoadBalancer {
+        WeightedLoadBalancer::with_weight_key(DEFAULT_WEIGHT_KEY)
+    }
+
+    pub fn with_weight_key(weight_key: &str) -> WeightedLoadBalancer {
+        WeightedLoadBalancer {
+            weight_key: weight_key.to_owned(),
+        }
+    }
+
+    fn weight(&self, instance: &Instance) -> u64 {
+        match instance.metadata.get(&self.weight_key) {
+            Some(Value::Number(n)) => n.as_u64().unwrap_or(1),
+            Some(Value::String(s)) => s.trim().parse().unwrap_or(1),
+            _ => 1,
+        }
+    }
+}
+
+impl Default for WeightedLoadBalancer {
+    fn default() -> Self {
+        WeightedLoadBalancer::new()
+    }
+}
+
+impl LoadBalancer for WeightedLoadBalancer {
+    fn choose<'a>(&self, candidates: &[&'a Instance]) -> Option<&'a Instance> {
+        let weights = candidates
+            .iter()
+            .map(|i| self.weight(i))
+            .collect::<Vec<_>>();
+        let total = weights.iter().copied().fold(0u64, u64::saturating_add);
+        if total == 0 {
+            return None;
+        }
+        let mut pick = fastrand::u64(..total);
+        for (instance, weight) in candidates.iter().zip(weights) {
+            if pick < weight {
+                return Some(instance);
+            }
+            pick -= weight;
+        }
+        None
+    }
+}
+
+/// Picks the candidate with the fewest requests in flight. Callers report in-flight
+/// requests by holding the [RequestGuard](struct.RequestGuard.html) returned from `start`.
+#[derive(Debug, Default, Clone)]
+pub struct LeastOutstandingRequestsLoadBalancer {
+    outstanding: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl LeastOutstandingRequestsLoadBalancer {
+    pub fn new() -> LeastOutstandingRequestsLoadBalancer {
+        LeastOutstandingRequestsLoadBalancer::default()
+    }
+
+    /// Records the start of a request to the instance, which ends when the guard is dropped
+    pub fn start(&self, instance: &Instance) -> RequestGuard {
+        let key = instance_key(instance);
+        if let Ok(mut outstanding) = self.outstanding.lock() {
+            *outstanding.entry(key.clone()).or_insert(0) += 1;
+        }
+        RequestGuard {
+            outstanding: self.outstanding.clone(),
+            key,
+        }
+    }
+
+    /// The number of requests currently in flight to the instance
+    pub fn outstanding(&self, instance: &Instance) -> usize {
+        self.outstanding
+            .lock()
+            .ok()
+            .and_then(|o| o.get(&instance_key(instance)).copied())
+            .unwrap_or(0)
+    }
+}
+
+impl LoadBalancer for LeastOutstandingRequestsLoadBalancer {
+    fn choose<'a>(&self, candidates: &[&'a Instance]) -> Option<&'a Instance> {
+        let outstanding = self.outstanding.lock().ok()?;
+        let count = |i: &Instance| outstanding.get(&instance_key(i)).copied().unwrap_or(0);
+        let min = candidates.iter().map(|i| count(i)).min()?;
+        // break ties randomly so idle instances share the load
+        let idle = candidates
+            .iter()
+            .filter(|i| count(i) == min)
+            .collect::<Vec<_>>();
+        Some(idle[fastrand::usize(..idle.len())])
+    }
+}
+
+/// Marks a request as finished when dropped
+#[derive(Debug)]
+pub struct RequestGuard {
+    outstanding: Arc<Mutex<HashMap<String, usize>>>,
+    key: String,
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        if let Ok(mut outstanding) = self.outstanding.lock() {
+            if let Some(count) = outstanding.get_mut(&self.key) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    outstanding.remove(&self.key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::build_test_instance;
+
+    fn build_test_application() -> Application {
+        let mut down = build_test_instance();
+        down.host_name = "down".to_owned();
+        down.status = Status::Down;
+        let mut one = build_test_instance();
+        one.host_name = "one".to_owned();
+        let mut two = build_test_instance();
+        two.host_name = "two".to_owned();
+        Application {
+            name: "test_app".into(),
+            instance: vec![down, one, two],
+        }
+    }
+
+    #[test]
+    fn test_base_url() {
+        let mut instance = build_test_instance();
+        assert_eq!(Some("http://Foo:80".to_owned()), base_url(&instance, false));
+        assert_eq!(
+            Some("https://Foo:443".to_owned()),
+            base_url(&instance, true)
+        );
+        instance.port = None;
+        assert_eq!(
+            Some("https://Foo:443".to_owned()),
+            base_url(&instance, false)
+        );
+        instance.secure_port = None;
+        assert_eq!(None, base_url(&instance, false));
+
+        instance.host_name = "::1".to_owned();
+        instance.port = Some(8080);
+        let url = base_url(&instance, false).expect("test unwrap");
+        assert_eq!("http://[::1]:8080", url);
+        let url = reqwest::Url::parse(&url).expect("test unwrap");
+        assert_eq!(Some("[::1]"), url.host_str());
+        assert_eq!(Some(8080), url.port());
+    }
+
+    #[test]
+    fn test_round_robin_skips_down_instances() {
+        let app = build_test_application();
+        let lb = RoundRobinLoadBalancer::new();
+        let picked = (0..4)
+            .map(|_| lb.select(&app).expect("an instance").host_name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["one", "two", "one", "two"], picked);
+        assert_eq!(Some("http://one:80".to_owned()), lb.select_url(&app, false));
+    }
+
+    #[test]
+    fn test_no_up_instances() {
+        let mut app = build_test_application();
+        app.instance
+            .iter_mut()
+            .for_each(|i| i.status = Status::OutOfService);
+        assert!(RandomLoadBalancer::new().select(&app).is_none());
+
+        // instances reporting UP but taken out of service remotely are skipped too
+        app.instance.iter_mut().for_each(|i| {
+            i.status = Status::Up;
+            i.overriddenstatus = Some(Status::OutOfService);
+        });
+        assert!(RandomLoadBalancer::new().select(&app).is_none());
+    }
+
+    #[test]
+    fn test_weighted() {
+        let mut app = build_test_application();
+        app.instance[1]
+            .metadata
+            .insert("weight".to_owned(), Value::String("0".to_owned()));
+        let lb = WeightedLoadBalancer::new();
+        for _ in 0..20 {
+            assert_eq!("two", lb.select(&app).expect("an instance").host_name);
+        }
+    }
+
+    #[test]
+    fn test_weighted_max_weights() {
+        let mut app = build_test_application();
+        for instance in app.instance.iter_mut() {
+            instance
+                .metadata
+                .insert("weight".to_owned(), Value::from(u64::MAX));
+        }
+        let lb = WeightedLoadBalancer::new();
+        for _ in 0..20 {
+            assert!(lb.select(&app).is_some());
+        }
+    }
+
+    #[test]
+    fn test_least_outstanding_requests() {
+        let app = build_test_application();
+        let lb = LeastOutstandingRequestsLoadBalancer::new();
+        let busy = lb.start(&app.instance[1]);
+        assert_eq!(1, lb.outstanding(&app.instance[1]));
+        assert_eq!("two", lb.select(&app).expect("an instance").host_name);
+        drop(busy);
+        assert_eq!(0, lb.outstanding(&app.instance[1]));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::errors::EurekaClientError;
+use crate::load_balancer::{base_url, instance_key, is_up, LoadBalancer};
+use crate::outlier::OutlierDetector;
+use crate::registry::CachedRegistry;
+use crate::response::Instance;
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::{Method, Request, Response, Url};
+use reqwest_middleware::{Error, Middleware, Next, Result};
+use std::sync::Arc;
+
+/// How many other instances an idempotent request is retried on after a connection failure
+pub const DEFAULT_MAX_RETRIES: usize = 1;
+
+/// A [reqwest-middleware](https://docs.rs/reqwest-middleware) middleware that treats the host
+/// of each request url as a Eureka app or VIP name, e.g. `http://INVENTORY-SERVICE/items`,
+/// and sends the request to an instance chosen by the load balancer instead.
+///
+/// Hosts Eureka does not know, or that fail to resolve, are sent unchanged. Idempotent requests that fail to
+/// connect are retried on a different instance.
+///
+/// With an [OutlierDetector](../outlier/struct.OutlierDetector.html), the outcome of every
+/// request is recorded and ejected instances are left out: requests that fail to be sent
+/// or get a `5xx` response count as failures.
+pub struct EurekaMiddleware<L> {
+    registry: Arc<CachedRegistry>,
+    load_balancer: L,
+    max_retries: usize,
+    outlier_detector: Option<OutlierDetector>,
+}
+
+impl<L: LoadBalancer> EurekaMiddleware<L> {
+    /// Creates a new middleware
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The registry used to resolve host names
+    /// * `load_balancer` - Chooses between the `UP` instances of an application
+    pub fn new(registry: Arc<CachedRegistry>, load_balancer: L) -> EurekaMiddleware<L> {
+        EurekaMiddleware {
+            registry,
+            load_balancer,
+            max_retries: DEFAULT_MAX_RETRIES,
+            outlier_detector: None,
+        }
+    }
+
+    pub fn max_retries(mut self, max_retries: usize) -> EurekaMiddleware<L> {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Records the outcome of every request with the detector and skips ejected instances
+    pub fn outlier_detector(mut self, detector: OutlierDetector) -> EurekaMiddleware<L> {
+        self.outlier_detector = Some(detector);
+        self
+    }
+}
+
+#[async_trait]
+impl<L: LoadBalancer + 'static> Middleware for EurekaMiddleware<L> {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let name = match req.url().host_str() {
+            Some(host) => host.to_owned(),
+            None => return next.run(req, extensions).await,
+        };
+        let application = match self.registry.resolve(&name).await {
+            Ok(Some(app)) => app,
+            Ok(None) => return next.run(req, extensions).await,
+            Err(e) => {
+                warn!(
+                    "failed to resolve {}, sending the request unchanged: {}",
+                    name, e
+                );
+                return next.run(req, extensions).await;
+            }
+        };
+
+        let secure = req.url().scheme() == "https";
+        let idempotent = is_idempotent(req.method());
+        let mut tried = Vec::new();
+        let mut last_error = None;
+        loop {
+            // the ejection cap applies to every instance of the app, so tried instances are
+            // only left out afterwards
+            let mut candidates = application
+                .instance
+                .iter()
+                .filter(|i| is_up(i))
+                .collect::<Vec<_>>();
+            if let Some(detector) = &self.outlier_detector {
+                candidates = detector.filter(&candidates);
+            }
+            candidates.retain(|i| !tried.contains(&instance_key(i)));
+            let instance = match self.load_balancer.choose(&candidates) {
+                Some(instance) => instance,
+                // every instance was tried, so the last connection error tells what went wrong
+                None => {
+                    return Err(last_error.unwrap_or_else(|| {
+                        Error::middleware(EurekaClientError::GenericError(format!(
+                            "No UP instance available for {}",
+                            name
+                        )))
+                    }))
+                }
+            };
+            tried.push(instance_key(instance));
+
+            let url = rewrite_url(req.url(), instance, secure).ok_or_else(|| {
+                Error::middleware(EurekaClientError::GenericError(format!(
+                    "Instance {} of {} has no usable port",
+                    instance.host_name, name
+                )))
+            })?;
+            debug!("resolved {} to {}", name, url);
+            *req.url_mut() = url;
+
+            // streaming bodies can not be cloned, so those requests are only sent once
+            let retry = if idempotent && tried.len() <= self.max_retries {
+                req.try_clone()
+            } else {
+                None
+            };
+
+            let res = next.clone().run(req, extensions).await;
+            if let Some(detector) = &self.outlier_detector {
+                match &res {
+                    Ok(res) if !res.status().is_server_error() => detector.record_success(instance),
+                    _ => detector.record_failure(instance),
+                }
+            }
+
+            match (res, retry) {
+                (Err(Error::Reqwest(e)), Some(retry)) if e.is_connect() => {
+                    warn!(
+                        "connection to {} failed, retrying on another instance: {}",
+                        instance.host_name, e
+                    );
+                    req = retry;
+                    last_error = Some(Error::Reqwest(e));
+                }
+                (res, _) => return res,
+            }
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE | Method::TRACE
+    )
+}
+
+/// Replaces the scheme, host and port of the url with those of the instance
+fn rewrite_url(url: &Url, instance: &Instance, secure: bool) -> Option<Url> {
+    let base = Url::parse(&base_url(instance, secure)?).ok()?;
+    let mut url = url.clone();
+    url.set_scheme(base.scheme()).ok()?;
+    url.set_host(base.host_str()).ok()?;
+    url.set_port(base.port()).ok()?;
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::build_test_instance;
+
+    #[test]
+    fn test_rewrite_url() {
+        let instance = build_test_instance();
+        let url = Url::parse("http://INVENTORY-SERVICE/items?page=2").expect("test unwrap");
+        assert_eq!(
+            "http://foo/items?page=2",
+            rewrite_url(&url, &instance, false)
+                .expect("test unwrap")
+                .as_str()
+        );
+
+        let url = Url::parse("https://inventory-service:8443/items").expect("test unwrap");
+        assert_eq!(
+            "https://foo/items",
+            rewrite_url(&url, &instance, true)
+                .expect("test unwrap")
+                .as_str()
+        );
+    }
+
+    #[test]
+    fn test_is_idempotent() {
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::DELETE));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+}
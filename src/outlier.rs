@@ -0,0 +1,351 @@
+use crate::load_balancer::{instance_key, LoadBalancer};
+use crate::response::Instance;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The number of consecutive failures after which an instance is ejected
+pub const DEFAULT_CONSECUTIVE_FAILURES: u32 = 5;
+/// How long an instance is ejected the first time
+pub const DEFAULT_BASE_EJECTION_TIME: Duration = Duration::from_secs(30);
+/// The longest an instance is ejected, however often it was ejected before
+pub const DEFAULT_MAX_EJECTION_TIME: Duration = Duration::from_secs(300);
+/// The share of an application's instances that can be ejected at once
+pub const DEFAULT_MAX_EJECTION_PERCENT: u8 = 50;
+
+#[derive(Debug, Default)]
+struct Outlier {
+    consecutive_failures: u32,
+    ejections: u32,
+    last_failure: Option<Instant>,
+    ejected_at: Option<Instant>,
+    ejected_until: Option<Instant>,
+}
+
+impl Outlier {
+    fn is_ejected(&self, now: Instant) -> bool {
+        self.ejected_until.is_some_and(|until| until > now)
+    }
+
+    // not ejected and quiet for longer than an ejection can last, so its failures and
+    // ejections no longer matter
+    fn is_expired(&self, now: Instant, max_ejection_time: Duration) -> bool {
+        let quiet_since = self.last_failure.max(self.ejected_until);
+        !self.is_ejected(now)
+            && quiet_since
+                .is_none_or(|since| now.saturating_duration_since(since) > max_ejection_time)
+    }
+}
+
+/// Passive outlier detection: ejects instances that keep failing from selection, for
+/// instances that report `UP` to Eureka while refusing connections until their lease
+/// expires.
+///
+/// Callers feed it the outcome of every call, keyed by the instance's host and port. After
+/// `consecutive_failures` failures in a row an instance is ejected for the base ejection
+/// time, doubled on each ejection that follows without a success in between, up to the
+/// maximum ejection time. At most `max_ejection_percent` of the candidates of an
+/// application are ejected at once, those ejected first; the others are selected again.
+///
+/// Selection consults it through an
+/// [OutlierAwareLoadBalancer](struct.OutlierAwareLoadBalancer.html) or an
+/// [EurekaDiscover](../discover/struct.EurekaDiscover.html), and the
+/// [EurekaMiddleware](../middleware/struct.EurekaMiddleware.html) both feeds and consults
+/// it. Clones share their state, so one detector can be fed and consulted from several
+/// places. Instances that were not ejected nor failed for longer than the maximum ejection
+/// time are forgotten.
+#[derive(Debug, Clone)]
+pub struct OutlierDetector {
+    consecutive_failures: u32,
+    base_ejection_time: Duration,
+    max_ejection_time: Duration,
+    max_ejection_percent: u8,
+    outliers: Arc<Mutex<HashMap<String, Outlier>>>,
+}
+
+impl OutlierDetector {
+    /// Creates a detector with the default thresholds
+    pub fn new() -> OutlierDetector {
+        OutlierDetector {
+            consecutive_failures: DEFAULT_CONSECUTIVE_FAILURES,
+            base_ejection_time: DEFAULT_BASE_EJECTION_TIME,
+            max_ejection_time: DEFAULT_MAX_EJECTION_TIME,
+            max_ejection_percent: DEFAULT_MAX_EJECTION_PERCENT,
+            outliers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn consecutive_failures(mut self, failures: u32) -> OutlierDetector {
+        self.consecutive_failures = failures.max(1);
+        self
+    }
+
+    pub fn base_ejection_time(mut self, time: Duration) -> OutlierDetector {
+        self.base_ejection_time = time;
+        self
+    }
+
+    pub fn max_ejection_time(mut self, time: Duration) -> OutlierDetector {
+        self.max_ejection_time = time;
+        self
+    }
+
+    /// The share of an application's instances that can be ejected at once, capped at 100
+    pub fn max_ejection_percent(mut self, percent: u8) -> OutlierDetector {
+        self.max_ejection_percent = percent.min(100);
+        self
+    }
+
+    /// Records a successful call, which resets the failures and ejections of the instance
+    pub fn record_success(&self, instance: &Instance) {
+        if let Ok(mut outliers) = self.outliers.lock() {
+            outliers.remove(&instance_key(instance));
+        }
+    }
+
+    /// Records a failed call, ejecting the instance once it failed too many times in a row.
+    /// Failures recorded while the instance is ejected are not counted.
+    pub fn record_failure(&self, instance: &Instance) {
+        self.record_failure_at(instance, Instant::now());
+    }
+
+    /// Whether the instance is currently ejected, regardless of the ejection cap
+    pub fn is_ejected(&self, instance: &Instance) -> bool {
+        let now = Instant::now();
+        self.outliers
+            .lock()
+            .ok()
+            .and_then(|o| o.get(&instance_key(instance)).map(|o| o.is_ejected(now)))
+            .unwrap_or(false)
+    }
+
+    /// The candidates of one application that are not ejected, letting back in the
+    /// instances ejected last when more than the allowed share of them are ejected
+    pub fn filter<'a>(&self, candidates: &[&'a Instance]) -> Vec<&'a Instance> {
+        self.filter_at(candidates, Instant::now())
+    }
+
+    fn record_failure_at(&self, instance: &Instance, now: Instant) {
+        let Ok(mut outliers) = self.outliers.lock() else {
+            return;
+        };
+        outliers.retain(|_, o| !o.is_expired(now, self.max_ejection_time));
+        let key = instance_key(instance);
+        let outlier = outliers.entry(key).or_default();
+        if outlier.is_ejected(now) {
+            return;
+        }
+        outlier.last_failure = Some(now);
+        outlier.consecutive_failures += 1;
+        if outlier.consecutive_failures >= self.consecutive_failures {
+            outlier.consecutive_failures = 0;
+            outlier.ejections += 1;
+            let ejection_time = self
+                .base_ejection_time
+                .saturating_mul(2u32.saturating_pow(outlier.ejections - 1))
+                .min(self.max_ejection_time);
+            outlier.ejected_at = Some(now);
+            outlier.ejected_until = Some(now + ejection_time);
+            warn!(
+                "ejecting {} for {:?} after {} consecutive failures",
+                instance_key(instance),
+                ejection_time,
+                self.consecutive_failures
+            );
+        }
+    }
+
+    fn filter_at<'a>(&self, candidates: &[&'a Instance], now: Instant) -> Vec<&'a Instance> {
+        let Ok(outliers) = self.outliers.lock() else {
+            return candidates.to_vec();
+        };
+        let mut ejected = candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(n, instance)| {
+                let outlier = outliers.get(&instance_key(instance))?;
+                if outlier.is_ejected(now) {
+                    outlier.ejected_at.map(|at| (at, n))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        ejected.sort();
+        ejected.truncate(candidates.len() * usize::from(self.max_ejection_percent) / 100);
+
+        candidates
+            .iter()
+            .enumerate()
+            .filter(|(n, _)| !ejected.iter().any(|(_, e)| e == n))
+            .map(|(_, instance)| *instance)
+            .collect()
+    }
+}
+
+impl Default for OutlierDetector {
+    fn default() -> Self {
+        OutlierDetector::new()
+    }
+}
+
+/// Wraps a [LoadBalancer](../load_balancer/trait.LoadBalancer.html), leaving out the
+/// instances the [OutlierDetector](struct.OutlierDetector.html) ejected
+pub struct OutlierAwareLoadBalancer<L> {
+    detector: OutlierDetector,
+    inner: L,
+}
+
+impl<L: LoadBalancer> OutlierAwareLoadBalancer<L> {
+    pub fn new(detector: OutlierDetector, inner: L) -> OutlierAwareLoadBalancer<L> {
+        OutlierAwareLoadBalancer { detector, inner }
+    }
+
+    pub fn detector(&self) -> &OutlierDetector {
+        &self.detector
+    }
+}
+
+impl<L: LoadBalancer> LoadBalancer for OutlierAwareLoadBalancer<L> {
+    fn choose<'a>(&self, candidates: &[&'a Instance]) -> Option<&'a Instance> {
+        self.inner.choose(&self.detector.filter(candidates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_balancer::RoundRobinLoadBalancer;
+    use crate::response::{build_test_instance, Application};
+
+    fn instances(ports: &[u16]) -> Vec<Instance> {
+        ports
+            .iter()
+            .map(|port| {
+                let mut instance = build_test_instance();
+                instance.port = Some(*port);
+                instance
+            })
+            .collect()
+    }
+
+    fn ports(instances: &[&Instance]) -> Vec<Option<u16>> {
+        instances.iter().map(|i| i.port).collect()
+    }
+
+    #[test]
+    fn test_ejection_backs_off() {
+        let detector = OutlierDetector::new()
+            .consecutive_failures(2)
+            .base_ejection_time(Duration::from_secs(10))
+            .max_ejection_time(Duration::from_secs(25))
+            .max_ejection_percent(100);
+        let instances = instances(&[8080, 8081]);
+        let candidates = instances.iter().collect::<Vec<_>>();
+        let start = Instant::now();
+
+        detector.record_failure_at(&instances[0], start);
+        assert_eq!(2, detector.filter_at(&candidates, start).len());
+        detector.record_failure_at(&instances[0], start);
+        assert_eq!(
+            vec![Some(8081)],
+            ports(&detector.filter_at(&candidates, start))
+        );
+        // failures while ejected are not counted
+        detector.record_failure_at(&instances[0], start + Duration::from_secs(5));
+        assert_eq!(
+            2,
+            detector
+                .filter_at(&candidates, start + Duration::from_secs(10))
+                .len()
+        );
+
+        // ejected again, for twice as long, and then for the maximum
+        let later = start + Duration::from_secs(10);
+        detector.record_failure_at(&instances[0], later);
+        detector.record_failure_at(&instances[0], later);
+        let filtered = detector.filter_at(&candidates, later + Duration::from_secs(19));
+        assert_eq!(vec![Some(8081)], ports(&filtered));
+        let later = later + Duration::from_secs(20);
+        assert_eq!(2, detector.filter_at(&candidates, later).len());
+        detector.record_failure_at(&instances[0], later);
+        detector.record_failure_at(&instances[0], later);
+        let filtered = detector.filter_at(&candidates, later + Duration::from_secs(24));
+        assert_eq!(vec![Some(8081)], ports(&filtered));
+        assert_eq!(
+            2,
+            detector
+                .filter_at(&candidates, later + Duration::from_secs(25))
+                .len()
+        );
+
+        // a success resets the instance
+        let later = later + Duration::from_secs(25);
+        detector.record_failure_at(&instances[1], later);
+        detector.record_success(&instances[1]);
+        detector.record_failure_at(&instances[1], later);
+        assert_eq!(2, detector.filter_at(&candidates, later).len());
+    }
+
+    #[test]
+    fn test_max_ejection_percent() {
+        let detector = OutlierDetector::new().consecutive_failures(1);
+        let instances = instances(&[8080, 8081, 8082, 8083]);
+        let candidates = instances.iter().collect::<Vec<_>>();
+        let start = Instant::now();
+        for (n, instance) in instances.iter().take(3).enumerate() {
+            detector.record_failure_at(instance, start + Duration::from_millis(n as u64));
+        }
+        assert!(detector.is_ejected(&instances[2]));
+
+        // only half of them are ejected, the first two
+        let now = start + Duration::from_secs(1);
+        assert_eq!(
+            vec![Some(8082), Some(8083)],
+            ports(&detector.filter_at(&candidates, now))
+        );
+        // a single instance is never ejected
+        assert_eq!(1, detector.filter_at(&candidates[..1], now).len());
+    }
+
+    #[test]
+    fn test_outlier_aware_load_balancer() {
+        let application = Application {
+            name: "BAR".into(),
+            instance: instances(&[8080, 8081]),
+        };
+        let lb = OutlierAwareLoadBalancer::new(
+            OutlierDetector::new().consecutive_failures(1),
+            RoundRobinLoadBalancer::new(),
+        );
+        lb.detector().record_failure(&application.instance[0]);
+        for _ in 0..4 {
+            assert_eq!(
+                Some(8081),
+                lb.select(&application).expect("an instance").port
+            );
+        }
+    }
+
+    #[test]
+    fn test_forgets_quiet_instances() {
+        let detector = OutlierDetector::new()
+            .consecutive_failures(2)
+            .max_ejection_time(Duration::from_secs(60));
+        let instances = instances(&[8080, 8081]);
+        let start = Instant::now();
+        detector.record_failure_at(&instances[0], start);
+        detector.record_failure_at(&instances[1], start);
+        detector.record_failure_at(&instances[1], start);
+        assert_eq!(2, detector.outliers.lock().expect("test unwrap").len());
+
+        // the failure of 8080 expired, 8081 is still remembered after its ejection
+        detector.record_failure_at(&instances[1], start + Duration::from_secs(61));
+        let outliers = detector.outliers.lock().expect("test unwrap");
+        assert_eq!(
+            vec![&instance_key(&instances[1])],
+            outliers.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(1, outliers[&instance_key(&instances[1])].ejections);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::errors::EurekaClientError;
+use crate::response::ApplicationsResponse;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Where and when a persisted snapshot was fetched
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotHeader {
+    /// Milliseconds since the epoch, like Eureka's own timestamps
+    pub fetched_at: i64,
+    /// The url of the server that answered
+    pub source: String,
+}
+
+/// A registry response saved to disk, in Eureka's JSON format under a small header
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedSnapshot {
+    pub header: SnapshotHeader,
+    pub response: ApplicationsResponse,
+}
+
+// keeps the temporary files of concurrent saves apart within a process
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// serializes like PersistedSnapshot without cloning the response
+#[derive(Serialize)]
+struct PersistedSnapshotRef<'a> {
+    header: SnapshotHeader,
+    response: &'a ApplicationsResponse,
+}
+
+/// A local file holding the last good registry response, so discovery can start from it
+/// while Eureka is unreachable
+#[derive(Debug, Clone)]
+pub struct SnapshotFile {
+    path: PathBuf,
+}
+
+impl SnapshotFile {
+    pub fn new<P: Into<PathBuf>>(path: P) -> SnapshotFile {
+        SnapshotFile { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Saves the response, replacing the previous snapshot atomically: it is written to a
+    /// uniquely named temporary file next to the snapshot, flushed to disk and renamed over
+    /// it, and the rename is flushed by syncing the directory
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - The response of a successful fetch
+    /// * `source` - The url of the server that answered
+    pub async fn save(
+        &self,
+        response: &ApplicationsResponse,
+        source: &str,
+    ) -> Result<(), EurekaClientError> {
+        let snapshot = PersistedSnapshotRef {
+            header: SnapshotHeader {
+                fetched_at: now_millis(),
+                source: source.to_owned(),
+            },
+            response,
+        };
+        let json = serde_json::to_vec(&snapshot)?;
+
+        let mut tmp_name = self.path.as_os_str().to_owned();
+        tmp_name.push(format!(
+            ".{}.{}.tmp",
+            process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp_path = PathBuf::from(tmp_name);
+        if let Err(e) = write_synced(&tmp_path, &json).await {
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(e.into());
+        }
+        fs::rename(&tmp_path, &self.path).await?;
+        sync_parent(&self.path).await?;
+        Ok(())
+    }
+
+    /// Loads the saved snapshot, or `None` when nothing was saved yet
+    pub async fn load(&self) -> Result<Option<PersistedSnapshot>, EurekaClientError> {
+        match fs::read(&self.path).await {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(EurekaClientError::from(e)),
+        }
+    }
+}
+
+async fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::File::create(path).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await
+}
+
+// directories can only be opened and synced on unix
+#[cfg(unix)]
+async fn sync_parent(path: &Path) -> std::io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::File::open(parent).await?.sync_all().await
+}
+
+#[cfg(not(unix))]
+async fn sync_parent(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{build_test_instance, Application, Applications};
+
+    #[tokio::test]
+    async fn test_save_and_load() {
+        let dir = tempfile::tempdir().expect("test unwrap");
+        let file = SnapshotFile::new(dir.path().join("registry.json"));
+        assert_eq!(None, file.load().await.expect("test unwrap"));
+
+        let response = ApplicationsResponse::new(Applications {
+            versions_delta: 1,
+            apps_hashcode: "UP_2_".to_owned(),
+            applications: vec![Application {
+                name: "BAR".into(),
+                instance: vec![build_test_instance(), build_test_instance()],
+            }],
+        });
+        file.save(&response, "http://eureka:8761/eureka/v2/apps")
+            .await
+            .expect("test unwrap");
+
+        let snapshot = file
+            .load()
+            .await
+            .expect("test unwrap")
+            .expect("test unwrap");
+        assert_eq!(response, snapshot.response);
+        assert_eq!("http://eureka:8761/eureka/v2/apps", snapshot.header.source);
+        assert!(snapshot.header.fetched_at > 0);
+        // only the snapshot is left behind
+        assert_eq!(
+            1,
+            std::fs::read_dir(dir.path()).expect("test unwrap").count()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_saves() {
+        let dir = tempfile::tempdir().expect("test unwrap");
+        let file = SnapshotFile::new(dir.path().join("registry.json"));
+        let response = ApplicationsResponse::new(Applications {
+            versions_delta: 1,
+            apps_hashcode: "UP_1_".to_owned(),
+            applications: vec![Application {
+                name: "BAR".into(),
+                instance: vec![build_test_instance()],
+            }],
+        });
+
+        let (first, second) = tokio::join!(
+            file.save(&response, "http://eureka-a:8761/eureka/v2/apps"),
+            file.save(&response, "http://eureka-b:8761/eureka/v2/apps")
+        );
+        first.expect("test unwrap");
+        second.expect("test unwrap");
+        let snapshot = file
+            .load()
+            .await
+            .expect("test unwrap")
+            .expect("test unwrap");
+        assert_eq!(response, snapshot.response);
+        assert_eq!(
+            1,
+            std::fs::read_dir(dir.path()).expect("test unwrap").count()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_corrupt_file() {
+        let dir = tempfile::tempdir().expect("test unwrap");
+        let path = dir.path().join("registry.json");
+        std::fs::write(&path, "{\"header\":").expect("test unwrap");
+        assert!(matches!(
+            SnapshotFile::new(path).load().await,
+            Err(EurekaClientError::JsonError(_))
+        ));
+    }
+}
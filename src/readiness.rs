@@ -0,0 +1,122 @@
+use crate::errors::EurekaClientError;
+use crate::request::{RegisterRequest, Status};
+use crate::response;
+use crate::EurekaClient;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+/// How often [await_visible](struct.ReadinessHandle.html#method.await_visible) polls by default
+pub const DEFAULT_VISIBILITY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+impl EurekaClient {
+    /// Registers the instance as `STARTING`, returning a handle that switches it to `UP`
+    /// once the application is ready
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The app to register the instance under
+    /// * `registration` - The registration, whose status is replaced with `STARTING`
+    pub async fn register_starting(
+        self: &Arc<Self>,
+        application_id: &str,
+        mut registration: RegisterRequest,
+    ) -> Result<ReadinessHandle, EurekaClientError> {
+        registration.instance.status = Status::Starting;
+        self.register(application_id, &registration).await?;
+        Ok(ReadinessHandle {
+            client: self.clone(),
+            application_id: application_id.to_owned(),
+            instance_id: registration.instance.host_name,
+        })
+    }
+}
+
+/// Whether [mark_ready](struct.ReadinessHandle.html#method.mark_ready) switched the instance
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadyOutcome {
+    /// The instance was switched to `UP`
+    Ready,
+    /// The instance was left alone, as it is overridden remotely with this status
+    Overridden(response::Status),
+}
+
+/// Switches an instance registered as `STARTING` to `UP`
+pub struct ReadinessHandle {
+    client: Arc<EurekaClient>,
+    application_id: String,
+    instance_id: String,
+}
+
+impl ReadinessHandle {
+    /// The id used for status updates, by default the host name as Eureka does
+    pub fn instance_id(mut self, instance_id: &str) -> ReadinessHandle {
+        self.instance_id = instance_id.to_owned();
+        self
+    }
+
+    /// Switches the instance to `UP` through the status endpoint, unless an override such as
+    /// `OUT_OF_SERVICE` was set remotely in the meantime
+    pub async fn mark_ready(&self) -> Result<ReadyOutcome, EurekaClientError> {
+        if let Some(status_override) = self
+            .client
+            .conflicting_override(&self.application_id, &self.instance_id, &Status::Up)
+            .await
+        {
+            info!(
+                "not marking {} as UP while overridden as {:?}",
+                self.instance_id, status_override
+            );
+            return Ok(ReadyOutcome::Overridden(status_override));
+        }
+        self.client
+            .update_status(&self.application_id, &self.instance_id, &Status::Up)
+            .await?;
+        Ok(ReadyOutcome::Ready)
+    }
+
+    /// Waits for the readiness future to complete, then switches the instance to `UP`
+    pub async fn ready_when<F: Future<Output = ()>>(
+        &self,
+        readiness: F,
+    ) -> Result<ReadyOutcome, EurekaClientError> {
+        readiness.await;
+        self.mark_ready().await
+    }
+
+    /// Polls Eureka until the instance is listed as `UP`, failing once `timeout` has passed.
+    /// An instance overridden with another status, e.g. `OUT_OF_SERVICE`, is not visible.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait for the instance to become visible
+    /// * `poll_interval` - The time between lookups
+    pub async fn await_visible(
+        &self,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<(), EurekaClientError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.client.get_application(&self.application_id).await {
+                Ok(res) if res.application.instance.iter().any(|i| self.is_visible(i)) => {
+                    return Ok(())
+                }
+                Ok(_) | Err(EurekaClientError::NotFound) => {}
+                Err(e) => debug!("await_visible lookup failed: {}", e),
+            }
+            if Instant::now() + poll_interval > deadline {
+                return Err(EurekaClientError::GenericError(format!(
+                    "{} was not visible as UP after {:?}",
+                    self.instance_id, timeout
+                )));
+            }
+            time::sleep(poll_interval).await;
+        }
+    }
+
+    fn is_visible(&self, instance: &response::Instance) -> bool {
+        instance.id() == self.instance_id && instance.effective_status() == response::Status::Up
+    }
+}
@@ -0,0 +1,257 @@
+use crate::load_balancer::is_up;
+use crate::response::{intern, Application, Applications, Instance};
+use std::collections::HashMap;
+
+/// Region configuration for fetching other regions' registries, following Eureka's
+/// `region` and `fetchRemoteRegionsRegistry` properties.
+///
+/// Eureka serves the registries of remote regions merged into the local one, so the region
+/// of each instance is derived from its availability zone, as Eureka's own client does:
+/// `us-west-2a` is in `us-west-2` and `us-central1-a` in `us-central1`. Zones that do not
+/// follow this naming can be mapped explicitly, and instances without a zone are taken as
+/// local.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionConfig {
+    /// The region this client runs in
+    pub local_region: String,
+    /// The regions whose registries are fetched along with the local one, in preference order
+    pub remote_regions: Vec<String>,
+    /// The region of each zone not named after its region
+    pub zones: HashMap<String, String>,
+}
+
+impl RegionConfig {
+    /// Creates a configuration fetching only the local region
+    ///
+    /// # Arguments
+    ///
+    /// * `local_region` - The region this client runs in, e.g. `us-east-1`
+    pub fn new(local_region: &str) -> RegionConfig {
+        RegionConfig {
+            local_region: local_region.to_owned(),
+            remote_regions: Vec::new(),
+            zones: HashMap::new(),
+        }
+    }
+
+    /// Adds a remote region to fetch
+    pub fn remote_region(mut self, region: &str) -> RegionConfig {
+        if region != self.local_region && !self.remote_regions.iter().any(|r| r == region) {
+            self.remote_regions.push(region.to_owned());
+        }
+        self
+    }
+
+    /// Maps a zone to its region
+    pub fn zone(mut self, zone: &str, region: &str) -> RegionConfig {
+        self.zones.insert(zone.to_owned(), region.to_owned());
+        self
+    }
+
+    /// The region an instance belongs to, derived from its zone
+    pub fn region_of<'a>(&'a self, instance: &'a Instance) -> &'a str {
+        instance
+            .zone()
+            .and_then(|zone| {
+                self.zones
+                    .get(zone)
+                    .map(String::as_str)
+                    .or_else(|| zone_region(zone))
+            })
+            .unwrap_or(&self.local_region)
+    }
+
+    /// Whether the region is the local one
+    pub fn is_local(&self, region: &str) -> bool {
+        region == self.local_region
+    }
+
+    /// Tags every instance with its region and orders the instances of each application
+    /// by region, the local one first and the remote ones in preference order. Each
+    /// application stays one application, whatever the regions of its instances.
+    pub fn tag(&self, applications: &mut Applications) {
+        for application in applications.applications.iter_mut() {
+            for instance in application.instance.iter_mut() {
+                instance.region = Some(intern(self.region_of(instance)));
+            }
+            application
+                .instance
+                .sort_by_key(|i| self.rank(i.region.as_deref().unwrap_or_default()));
+        }
+    }
+
+    /// The `regions` query parameter, or `None` when only the local region is fetched
+    pub(crate) fn query(&self) -> Option<String> {
+        if self.remote_regions.is_empty() {
+            None
+        } else {
+            Some(format!("regions={}", self.remote_regions.join(",")))
+        }
+    }
+
+    // local first, then the remote regions in order, then regions that were not asked for
+    fn rank(&self, region: &str) -> usize {
+        if self.is_local(region) {
+            0
+        } else {
+            self.remote_regions
+                .iter()
+                .position(|r| r == region)
+                .map_or(usize::MAX, |p| p + 1)
+        }
+    }
+}
+
+/// The region of an AWS or GCP zone, e.g. `us-west-2` for `us-west-2a` and `us-central1`
+/// for `us-central1-a`, or `None` when the zone is not named after its region
+pub fn zone_region(zone: &str) -> Option<&str> {
+    let region = zone.trim_end_matches(|c: char| c.is_ascii_lowercase());
+    if region.len() == zone.len() {
+        return None;
+    }
+    let region = region.strip_suffix('-').unwrap_or(region);
+    if region.ends_with(|c: char| c.is_ascii_digit()) {
+        Some(region)
+    } else {
+        None
+    }
+}
+
+/// The instances of the application to use: those of the first region with an instance
+/// that is up, in the order [RegionConfig::tag](struct.RegionConfig.html#method.tag) sorts
+/// them, so the local region wins until it has none left. Untagged instances are all used.
+pub fn prefer_local(application: &Application) -> Vec<&Instance> {
+    let region = application
+        .instance
+        .iter()
+        .find(|i| is_up(i))
+        .or_else(|| application.instance.first())
+        .map(|i| i.region.as_deref());
+    match region {
+        Some(region) => application
+            .instance
+            .iter()
+            .filter(|i| i.region.as_deref() == region)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{build_test_instance, Status};
+    use serde_json::{Map, Value};
+
+    fn instance_in(zone: &str, host: &str) -> Instance {
+        let mut instance = build_test_instance();
+        instance.host_name = host.to_owned();
+        let mut custom = Map::new();
+        custom.insert("zone".to_owned(), Value::from(zone));
+        instance.data_center_info.metadata = None;
+        instance.data_center_info.custom_metadata = Some(custom);
+        instance
+    }
+
+    fn build_test_applications() -> Applications {
+        Applications {
+            versions_delta: 1,
+            apps_hashcode: String::new(),
+            applications: vec![Application {
+                name: "BAR".into(),
+                instance: vec![
+                    instance_in("eu-west-1a", "eu"),
+                    instance_in("us-east-1a", "local"),
+                    instance_in("us-west-2b", "west"),
+                    instance_in("us-east-1c", "local2"),
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_zone_region() {
+        assert_eq!(Some("us-west-2"), zone_region("us-west-2a"));
+        assert_eq!(Some("us-central1"), zone_region("us-central1-a"));
+        assert_eq!(None, zone_region("us-west-2"));
+        assert_eq!(None, zone_region("rack-a"));
+        assert_eq!(None, zone_region(""));
+    }
+
+    fn hosts(instances: &[&Instance]) -> Vec<String> {
+        instances.iter().map(|i| i.host_name.clone()).collect()
+    }
+
+    #[test]
+    fn test_tag_orders_per_region() {
+        let config = RegionConfig::new("us-east-1")
+            .remote_region("us-west-2")
+            .remote_region("eu-west-1")
+            .remote_region("us-east-1");
+        assert_eq!(
+            Some("regions=us-west-2,eu-west-1".to_owned()),
+            config.query()
+        );
+
+        let mut applications = build_test_applications();
+        config.tag(&mut applications);
+
+        assert_eq!(1, applications.applications.len());
+        let tagged = applications.applications[0]
+            .instance
+            .iter()
+            .map(|i| (i.host_name.as_str(), i.region.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("local", Some("us-east-1")),
+                ("local2", Some("us-east-1")),
+                ("west", Some("us-west-2")),
+                ("eu", Some("eu-west-1")),
+            ],
+            tagged
+        );
+    }
+
+    #[test]
+    fn test_region_of() {
+        let config = RegionConfig::new("us-east-1").zone("rack-a", "eu-west-1");
+        assert_eq!("eu-west-1", config.region_of(&instance_in("rack-a", "a")));
+        assert_eq!("us-east-1", config.region_of(&instance_in("rack-b", "b")));
+        assert_eq!(
+            "us-west-2",
+            config.region_of(&instance_in("us-west-2a", "c"))
+        );
+        assert_eq!(None, RegionConfig::new("us-east-1").query());
+
+        let mut instance = instance_in("rack-b", "d");
+        instance.data_center_info.custom_metadata = None;
+        instance
+            .metadata
+            .insert("zone".to_owned(), Value::from("eu-west-1b"));
+        assert_eq!("eu-west-1", config.region_of(&instance));
+    }
+
+    #[test]
+    fn test_prefer_local() {
+        let config = RegionConfig::new("us-east-1").remote_region("us-west-2");
+        let mut applications = build_test_applications();
+        config.tag(&mut applications);
+        let application = &mut applications.applications[0];
+        assert_eq!(vec!["local", "local2"], hosts(&prefer_local(application)));
+
+        for instance in application.instance.iter_mut().take(2) {
+            instance.status = Status::Down;
+        }
+        assert_eq!(vec!["west"], hosts(&prefer_local(application)));
+
+        for instance in application.instance.iter_mut() {
+            instance.status = Status::Down;
+        }
+        assert_eq!(vec!["local", "local2"], hosts(&prefer_local(application)));
+
+        // untagged instances are all used
+        let application = &build_test_applications().applications[0];
+        assert_eq!(4, prefer_local(application).len());
+    }
+}
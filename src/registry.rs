@@ -0,0 +1,186 @@
+use crate::errors::EurekaClientError;
+use crate::response::{intern, Application};
+use crate::static_registry::StaticRegistry;
+use crate::EurekaClient;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a lookup is cached, matching Eureka's default registry fetch interval
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+// a failed lookup keeps its error message, so it is not retried before the ttl expires
+type Entry = (Instant, Result<Option<Arc<Application>>, String>);
+
+/// Caches applications looked up by app or VIP name, refreshing them from Eureka
+/// once they are older than the ttl.
+///
+/// Names that are not app names are looked up by VIP address in a snapshot of the full
+/// registry, which is itself fetched at most once per ttl. Names Eureka does not know and
+/// failed lookups are cached for the ttl too.
+///
+/// A [StaticRegistry](../static_registry/struct.StaticRegistry.html) can back it up when
+/// every Eureka server fails, or serve as the only source.
+pub struct CachedRegistry {
+    client: Option<Arc<EurekaClient>>,
+    backup: Option<StaticRegistry>,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+    snapshot: Mutex<Option<(Instant, Arc<Vec<Application>>)>>,
+}
+
+impl CachedRegistry {
+    /// Creates a new registry with the default ttl
+    pub fn new(client: Arc<EurekaClient>) -> CachedRegistry {
+        CachedRegistry::with_ttl(client, DEFAULT_TTL)
+    }
+
+    /// Creates a new registry
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client used to query Eureka
+    /// * `ttl` - How long a lookup, including a failed one, is reused
+    pub fn with_ttl(client: Arc<EurekaClient>, ttl: Duration) -> CachedRegistry {
+        CachedRegistry {
+            client: Some(client),
+            backup: None,
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// Creates a registry that never queries Eureka and resolves names from the backup only
+    pub fn from_backup(backup: StaticRegistry) -> CachedRegistry {
+        CachedRegistry {
+            client: None,
+            backup: Some(backup),
+            ttl: DEFAULT_TTL,
+            entries: Mutex::new(HashMap::new()),
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// Resolves names from the backup when Eureka cannot be reached. The fallback result
+    /// is cached for the ttl like any other lookup.
+    pub fn with_backup(mut self, backup: StaticRegistry) -> CachedRegistry {
+        self.backup = Some(backup);
+        self
+    }
+
+    /// Looks up an application by its app name, falling back to the instances
+    /// whose VIP or secure VIP address matches the name.
+    /// Returns None when Eureka knows neither. A failed lookup fails again, with its
+    /// message in a `GenericError`, until the ttl expires.
+    pub async fn resolve(&self, name: &str) -> Result<Option<Arc<Application>>, EurekaClientError> {
+        let client = match &self.client {
+            Some(client) => client,
+            None => return Ok(self.backup.as_ref().and_then(|b| b.get(name))),
+        };
+        let key = name.to_uppercase();
+        if let Some(cached) = self.cached(&key) {
+            return cached;
+        }
+
+        let resolved = match self.resolve_remote(client, &key, name).await {
+            Ok(resolved) => Ok(resolved),
+            Err(e) => match &self.backup {
+                Some(backup) => {
+                    warn!(
+                        "failed to resolve {}, using the backup registry: {}",
+                        name, e
+                    );
+                    Ok(backup.get(name))
+                }
+                None => Err(e),
+            },
+        };
+
+        if let Ok(mut entries) = self.entries.lock() {
+            let entry = match &resolved {
+                Ok(app) => Ok(app.clone()),
+                Err(e) => Err(e.to_string()),
+            };
+            entries.insert(key, (Instant::now(), entry));
+        }
+        resolved
+    }
+
+    /// Drops the cached lookup for the name, so the next resolve goes to Eureka
+    pub fn invalidate(&self, name: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(&name.to_uppercase());
+        }
+    }
+
+    fn cached(&self, key: &str) -> Option<Result<Option<Arc<Application>>, EurekaClientError>> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(key)
+            .filter(|(fetched, _)| fetched.elapsed() < self.ttl)
+            .map(|(_, app)| app.clone().map_err(EurekaClientError::GenericError))
+    }
+
+    async fn resolve_remote(
+        &self,
+        client: &EurekaClient,
+        key: &str,
+        name: &str,
+    ) -> Result<Option<Arc<Application>>, EurekaClientError> {
+        let resolved = match client.get_application(key).await {
+            Ok(res) => Some(res.application),
+            Err(EurekaClientError::NotFound) => self.resolve_vip(client, name).await?,
+            Err(e) => return Err(e),
+        };
+        Ok(resolved.map(Arc::new))
+    }
+
+    async fn resolve_vip(
+        &self,
+        client: &EurekaClient,
+        vip: &str,
+    ) -> Result<Option<Application>, EurekaClientError> {
+        let applications = self.snapshot(client).await?;
+        let instance = applications
+            .iter()
+            .flat_map(|a| a.instance.iter())
+            .filter(|i| {
+                i.vip_address.eq_ignore_ascii_case(vip)
+                    || i.secure_vip_address.eq_ignore_ascii_case(vip)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if instance.is_empty() {
+            debug!("no application or vip found for {}", vip);
+            return Ok(None);
+        }
+        Ok(Some(Application {
+            name: intern(&vip.to_uppercase()),
+            instance,
+        }))
+    }
+
+    // the full registry, fetched again only once it is older than the ttl
+    async fn snapshot(
+        &self,
+        client: &EurekaClient,
+    ) -> Result<Arc<Vec<Application>>, EurekaClientError> {
+        if let Ok(snapshot) = self.snapshot.lock() {
+            if let Some((fetched, applications)) = snapshot.as_ref() {
+                if fetched.elapsed() < self.ttl {
+                    return Ok(applications.clone());
+                }
+            }
+        }
+        let applications = match client.get_applications().await {
+            Ok(apps) => Arc::new(apps.applications.applications),
+            Err(EurekaClientError::NotFound) => Arc::new(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            *snapshot = Some((Instant::now(), applications.clone()));
+        }
+        Ok(applications)
+    }
+}
@@ -1,5 +1,6 @@
 use serde::de::{Deserialize, Deserializer, Error as DeError, MapAccess, Visitor};
-use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
 use std::fmt;
 
 const AMI_LAUNCH_INDEX: &str = "ami-launch-index";
@@ -13,19 +14,10 @@ const LOCAL_IPV4: &str = "local-ipv4";
 const HOSTNAME: &str = "hostname";
 const AMI_ID: &str = "ami-id";
 const INSTANCE_TYPE: &str = "instance-type";
-const JSON_FIELDS: &[&str] = &[
-    AMI_LAUNCH_INDEX,
-    LOCAL_HOSTNAME,
-    AVAILABILITY_ZONE,
-    INSTANCE_ID,
-    PUBLIC_IPV4,
-    PUBLIC_HOSTNAME,
-    AMI_MANIFEST_PATH,
-    LOCAL_IPV4,
-    HOSTNAME,
-    AMI_ID,
-    INSTANCE_TYPE,
-];
+const IPV6: &str = "ipv6";
+const MAC: &str = "mac";
+const VPC_ID: &str = "vpc-id";
+const ACCOUNT_ID: &str = "accountId";
 const RUST_FIELDS: &[&str] = &[
     "ami_launch_index",
     "local_hostname",
@@ -38,22 +30,33 @@ const RUST_FIELDS: &[&str] = &[
     "hostname",
     "ami_id",
     "instance_type",
+    "ipv6",
+    "mac",
+    "vpc_id",
+    "account_id",
+    "other",
 ];
 const AMAZON_META_DATA: &str = "AmazonMetaData";
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 pub struct AmazonMetaData {
-    pub ami_launch_index: String,
+    pub ami_launch_index: Option<String>,
     pub local_hostname: String,
     pub availability_zone: String,
     pub instance_id: String,
-    pub public_ip4: String,
-    pub public_hostname: String,
-    pub ami_manifest_path: String,
+    pub public_ip4: Option<String>,
+    pub public_hostname: Option<String>,
+    pub ami_manifest_path: Option<String>,
     pub local_ip4: String,
-    pub hostname: String,
+    pub hostname: Option<String>,
     pub ami_id: String,
     pub instance_type: String,
+    pub ipv6: Option<String>,
+    pub mac: Option<String>,
+    pub vpc_id: Option<String>,
+    pub account_id: Option<String>,
+    /// Any keys not listed above, kept as-is
+    pub other: Map<String, Value>,
 }
 
 impl Serialize for AmazonMetaData {
@@ -61,18 +64,43 @@ impl Serialize for AmazonMetaData {
     where
         S: Serializer,
     {
-        let mut s = serializer.serialize_struct(AMAZON_META_DATA, 11)?;
-        s.serialize_field(AMI_LAUNCH_INDEX, &self.ami_launch_index)?;
-        s.serialize_field(LOCAL_HOSTNAME, &self.local_hostname)?;
-        s.serialize_field(AVAILABILITY_ZONE, &self.availability_zone)?;
-        s.serialize_field(INSTANCE_ID, &self.instance_id)?;
-        s.serialize_field(PUBLIC_IPV4, &self.public_ip4)?;
-        s.serialize_field(PUBLIC_HOSTNAME, &self.public_hostname)?;
-        s.serialize_field(AMI_MANIFEST_PATH, &self.ami_manifest_path)?;
-        s.serialize_field(LOCAL_IPV4, &self.local_ip4)?;
-        s.serialize_field(HOSTNAME, &self.hostname)?;
-        s.serialize_field(AMI_ID, &self.ami_id)?;
-        s.serialize_field(INSTANCE_TYPE, &self.instance_type)?;
+        let mut s = serializer.serialize_map(None)?;
+        if let Some(v) = &self.ami_launch_index {
+            s.serialize_entry(AMI_LAUNCH_INDEX, v)?;
+        }
+        s.serialize_entry(LOCAL_HOSTNAME, &self.local_hostname)?;
+        s.serialize_entry(AVAILABILITY_ZONE, &self.availability_zone)?;
+        s.serialize_entry(INSTANCE_ID, &self.instance_id)?;
+        if let Some(v) = &self.public_ip4 {
+            s.serialize_entry(PUBLIC_IPV4, v)?;
+        }
+        if let Some(v) = &self.public_hostname {
+            s.serialize_entry(PUBLIC_HOSTNAME, v)?;
+        }
+        if let Some(v) = &self.ami_manifest_path {
+            s.serialize_entry(AMI_MANIFEST_PATH, v)?;
+        }
+        s.serialize_entry(LOCAL_IPV4, &self.local_ip4)?;
+        if let Some(v) = &self.hostname {
+            s.serialize_entry(HOSTNAME, v)?;
+        }
+        s.serialize_entry(AMI_ID, &self.ami_id)?;
+        s.serialize_entry(INSTANCE_TYPE, &self.instance_type)?;
+        if let Some(v) = &self.ipv6 {
+            s.serialize_entry(IPV6, v)?;
+        }
+        if let Some(v) = &self.mac {
+            s.serialize_entry(MAC, v)?;
+        }
+        if let Some(v) = &self.vpc_id {
+            s.serialize_entry(VPC_ID, v)?;
+        }
+        if let Some(v) = &self.account_id {
+            s.serialize_entry(ACCOUNT_ID, v)?;
+        }
+        for (k, v) in &self.other {
+            s.serialize_entry(k, v)?;
+        }
         s.end()
     }
 }
@@ -94,6 +122,11 @@ impl<'de> Deserialize<'de> for AmazonMetaData {
             Hostname,
             AmiId,
             InstanceType,
+            Ipv6,
+            Mac,
+            VpcId,
+            AccountId,
+            Other(String),
         }
 
         impl<'de> Deserialize<'de> for Field {
@@ -126,7 +159,11 @@ impl<'de> Deserialize<'de> for AmazonMetaData {
                             HOSTNAME => Ok(Field::Hostname),
                             AMI_ID => Ok(Field::AmiId),
                             INSTANCE_TYPE => Ok(Field::InstanceType),
-                            _ => Err(DeError::unknown_field(v, JSON_FIELDS)),
+                            IPV6 => Ok(Field::Ipv6),
+                            MAC => Ok(Field::Mac),
+                            VPC_ID => Ok(Field::VpcId),
+                            ACCOUNT_ID => Ok(Field::AccountId),
+                            _ => Ok(Field::Other(v.to_owned())),
                         }
                     }
                 }
@@ -159,6 +196,11 @@ impl<'de> Deserialize<'de> for AmazonMetaData {
                 let mut maybe_hostname = None;
                 let mut maybe_ami_id = None;
                 let mut maybe_instance_type = None;
+                let mut maybe_ipv6 = None;
+                let mut maybe_mac = None;
+                let mut maybe_vpc_id = None;
+                let mut maybe_account_id = None;
+                let mut other = Map::new();
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -228,41 +270,64 @@ impl<'de> Deserialize<'de> for AmazonMetaData {
                             }
                             maybe_instance_type = Some(map.next_value()?)
                         }
+                        Field::Ipv6 => {
+                            if maybe_ipv6.is_some() {
+                                return Err(DeError::duplicate_field(IPV6));
+                            }
+                            maybe_ipv6 = Some(map.next_value()?)
+                        }
+                        Field::Mac => {
+                            if maybe_mac.is_some() {
+                                return Err(DeError::duplicate_field(MAC));
+                            }
+                            maybe_mac = Some(map.next_value()?)
+                        }
+                        Field::VpcId => {
+                            if maybe_vpc_id.is_some() {
+                                return Err(DeError::duplicate_field(VPC_ID));
+                            }
+                            maybe_vpc_id = Some(map.next_value()?)
+                        }
+                        Field::AccountId => {
+                            if maybe_account_id.is_some() {
+                                return Err(DeError::duplicate_field(ACCOUNT_ID));
+                            }
+                            maybe_account_id = Some(map.next_value()?)
+                        }
+                        Field::Other(name) => {
+                            other.insert(name, map.next_value()?);
+                        }
                     }
                 }
 
-                let ami_launch_index =
-                    maybe_ami_launch_index.ok_or_else(|| DeError::missing_field(AMI_LAUNCH_INDEX));
                 let local_hostname =
                     maybe_local_hostname.ok_or_else(|| DeError::missing_field(LOCAL_HOSTNAME));
                 let availability_zone = maybe_availability_zone
                     .ok_or_else(|| DeError::missing_field(AVAILABILITY_ZONE));
                 let instance_id =
                     maybe_instance_id.ok_or_else(|| DeError::missing_field(INSTANCE_ID));
-                let public_ip4 =
-                    maybe_public_ip4.ok_or_else(|| DeError::missing_field(PUBLIC_IPV4));
-                let public_hostname =
-                    maybe_public_hostname.ok_or_else(|| DeError::missing_field(PUBLIC_HOSTNAME));
-                let ami_manifest_path = maybe_ami_manifest_path
-                    .ok_or_else(|| DeError::missing_field(AMI_MANIFEST_PATH));
                 let local_ip4 = maybe_local_ip4.ok_or_else(|| DeError::missing_field(LOCAL_IPV4));
-                let hostname = maybe_hostname.ok_or_else(|| DeError::missing_field(HOSTNAME));
                 let ami_id = maybe_ami_id.ok_or_else(|| DeError::missing_field(AMI_ID));
                 let instance_type =
                     maybe_instance_type.ok_or_else(|| DeError::missing_field(INSTANCE_TYPE));
 
                 Ok(AmazonMetaData {
-                    ami_launch_index: ami_launch_index?,
+                    ami_launch_index: maybe_ami_launch_index,
                     local_hostname: local_hostname?,
                     availability_zone: availability_zone?,
                     instance_id: instance_id?,
-                    public_ip4: public_ip4?,
-                    public_hostname: public_hostname?,
-                    ami_manifest_path: ami_manifest_path?,
+                    public_ip4: maybe_public_ip4,
+                    public_hostname: maybe_public_hostname,
+                    ami_manifest_path: maybe_ami_manifest_path,
                     local_ip4: local_ip4?,
-                    hostname: hostname?,
+                    hostname: maybe_hostname,
                     ami_id: ami_id?,
                     instance_type: instance_type?,
+                    ipv6: maybe_ipv6,
+                    mac: maybe_mac,
+                    vpc_id: maybe_vpc_id,
+                    account_id: maybe_account_id,
+                    other,
                 })
             }
         }
@@ -278,17 +343,18 @@ pub mod test {
     #[test]
     fn test_serialize_amazon_meta_data() {
         let md = AmazonMetaData {
-            ami_launch_index: "001a".to_string(),
+            ami_launch_index: Some("001a".to_string()),
             local_hostname: "localhost0".to_string(),
             availability_zone: "US_East1a".to_string(),
             instance_id: "instance1a".to_string(),
-            public_ip4: "32.23.21.212".to_string(),
-            public_hostname: "foo.coma".to_string(),
-            ami_manifest_path: "/dev/nulla".to_string(),
+            public_ip4: Some("32.23.21.212".to_string()),
+            public_hostname: Some("foo.coma".to_string()),
+            ami_manifest_path: Some("/dev/nulla".to_string()),
             local_ip4: "127.0.0.12".to_string(),
-            hostname: "privatefoo.coma".to_string(),
+            hostname: Some("privatefoo.coma".to_string()),
             ami_id: "ami0023".to_string(),
             instance_type: "c4xlarged".to_string(),
+            ..Default::default()
         };
         let json = sample_meta_data();
 
@@ -299,23 +365,49 @@ pub mod test {
     #[test]
     fn test_deserialize_amazon_meta_data() {
         let md = AmazonMetaData {
-            ami_launch_index: "001a".to_string(),
+            ami_launch_index: Some("001a".to_string()),
             local_hostname: "localhost0".to_string(),
             availability_zone: "US_East1a".to_string(),
             instance_id: "instance1a".to_string(),
-            public_ip4: "32.23.21.212".to_string(),
-            public_hostname: "foo.coma".to_string(),
-            ami_manifest_path: "/dev/nulla".to_string(),
+            public_ip4: Some("32.23.21.212".to_string()),
+            public_hostname: Some("foo.coma".to_string()),
+            ami_manifest_path: Some("/dev/nulla".to_string()),
             local_ip4: "127.0.0.12".to_string(),
-            hostname: "privatefoo.coma".to_string(),
+            hostname: Some("privatefoo.coma".to_string()),
             ami_id: "ami0023".to_string(),
             instance_type: "c4xlarged".to_string(),
+            ..Default::default()
         };
         let json = sample_meta_data();
         let result = serde_json::from_str(&json).expect("failed to deserialize AmazonMetaData");
         assert_eq!(md, result);
     }
 
+    #[test]
+    fn test_optional_and_unknown_keys() {
+        let json = r#"{"local-hostname":"ip-10-0-0-12","availability-zone":"us-east-1a","instance-id":"i-0abc","local-ipv4":"10.0.0.12","ami-id":"ami-0023","instance-type":"m5.large","mac":"0e:11:22:33:44:55","accountId":"123456789012","kernel-id":"aki-1"}"#;
+        let mut other = Map::new();
+        other.insert("kernel-id".to_owned(), Value::String("aki-1".to_owned()));
+        let md = AmazonMetaData {
+            local_hostname: "ip-10-0-0-12".to_string(),
+            availability_zone: "us-east-1a".to_string(),
+            instance_id: "i-0abc".to_string(),
+            local_ip4: "10.0.0.12".to_string(),
+            ami_id: "ami-0023".to_string(),
+            instance_type: "m5.large".to_string(),
+            mac: Some("0e:11:22:33:44:55".to_string()),
+            account_id: Some("123456789012".to_string()),
+            other,
+            ..Default::default()
+        };
+
+        let result: AmazonMetaData =
+            serde_json::from_str(json).expect("deserialization should succeed");
+        assert_eq!(md, result);
+        let result = serde_json::to_string(&md).expect("serialization should succeed");
+        assert_eq!(json, result);
+    }
+
     pub fn sample_meta_data() -> String {
         r#"{ "ami-launch-index": "001a",
             "local-hostname": "localhost0",
@@ -0,0 +1,549 @@
+use super::validation::join_errors;
+use super::DataCenterInfo;
+use super::DcName;
+use super::Instance;
+use super::LeaseInfo;
+use super::Status;
+use super::ValidationError;
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+const DEFAULT_HOME_PAGE_PATH: &str = "/";
+const DEFAULT_STATUS_PAGE_PATH: &str = "/status";
+const DEFAULT_HEALTH_CHECK_PATH: &str = "/health";
+
+/// Which address family to consider when detecting the local IP address
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IpFamily {
+    V4,
+    V6,
+    Any,
+}
+
+/// Errors that can occur while building an [Instance](struct.Instance.html)
+#[derive(Debug)]
+pub enum InstanceBuilderError {
+    /// No app name was supplied
+    MissingApp,
+    /// Neither a port nor a secure port was supplied
+    MissingPort,
+    /// The host name was not supplied and could not be detected
+    HostNameUnavailable,
+    /// The local network interfaces could not be listed
+    InterfaceLookup(std::io::Error),
+    /// No non-loopback address matched the interface, CIDR and family selection
+    NoMatchingAddress,
+    /// The supplied CIDR block could not be parsed
+    InvalidCidr(String),
+    /// The built instance failed validation
+    Invalid(Vec<ValidationError>),
+}
+
+impl fmt::Display for InstanceBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InstanceBuilderError::MissingApp => write!(f, "An app name is required"),
+            InstanceBuilderError::MissingPort => {
+                write!(f, "Either a port or a secure port is required")
+            }
+            InstanceBuilderError::HostNameUnavailable => {
+                write!(f, "The host name could not be detected")
+            }
+            InstanceBuilderError::InterfaceLookup(e) => {
+                write!(f, "Failed to list network interfaces: {}", e)
+            }
+            InstanceBuilderError::NoMatchingAddress => {
+                write!(f, "No non-loopback address matched the selection")
+            }
+            InstanceBuilderError::InvalidCidr(s) => write!(f, "Invalid CIDR block: {}", s),
+            InstanceBuilderError::Invalid(errors) => {
+                write!(f, "Invalid instance: {}", join_errors(errors))
+            }
+        }
+    }
+}
+
+impl Error for InstanceBuilderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InstanceBuilderError::InterfaceLookup(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a request [Instance](struct.Instance.html), detecting the host name and IP address
+/// and deriving the home page, status page and health check urls from the port.
+///
+/// ```no_run
+/// use rust_eureka::request::InstanceBuilder;
+///
+/// let instance = InstanceBuilder::new("MY_SERVICE")
+///     .port(8080)
+///     .interface("eth0")
+///     .build()
+///     .expect("instance should build");
+/// ```
+#[derive(Debug)]
+pub struct InstanceBuilder {
+    app: String,
+    host_name: Option<String>,
+    ip_addr: Option<IpAddr>,
+    interface: Option<String>,
+    cidr: Option<String>,
+    ip_family: IpFamily,
+    prefer_ip_address: bool,
+    vip_address: Option<String>,
+    secure_vip_address: Option<String>,
+    status: Status,
+    port: Option<u16>,
+    secure_port: Option<u16>,
+    home_page_path: String,
+    status_page_path: String,
+    health_check_path: String,
+    data_center_info: Option<DataCenterInfo>,
+    lease_info: Option<LeaseInfo>,
+    metadata: Map<String, Value>,
+}
+
+impl InstanceBuilder {
+    /// Creates a new builder for the given app name
+    pub fn new(app: &str) -> InstanceBuilder {
+        InstanceBuilder {
+            app: app.to_owned(),
+            host_name: None,
+            ip_addr: None,
+            interface: None,
+            cidr: None,
+            ip_family: IpFamily::V4,
+            prefer_ip_address: false,
+            vip_address: None,
+            secure_vip_address: None,
+            status: Status::Up,
+            port: None,
+            secure_port: None,
+            home_page_path: DEFAULT_HOME_PAGE_PATH.to_owned(),
+            status_page_path: DEFAULT_STATUS_PAGE_PATH.to_owned(),
+            health_check_path: DEFAULT_HEALTH_CHECK_PATH.to_owned(),
+            data_center_info: None,
+            lease_info: None,
+            metadata: Map::new(),
+        }
+    }
+
+    /// Uses the given host name instead of detecting it
+    pub fn host_name(mut self, host_name: &str) -> Self {
+        self.host_name = Some(host_name.to_owned());
+        self
+    }
+
+    /// Uses the given IP address instead of detecting it
+    pub fn ip_addr(mut self, ip_addr: IpAddr) -> Self {
+        self.ip_addr = Some(ip_addr);
+        self
+    }
+
+    /// Only consider addresses of the named network interface, e.g. `eth0`
+    pub fn interface(mut self, interface: &str) -> Self {
+        self.interface = Some(interface.to_owned());
+        self
+    }
+
+    /// Only consider addresses within the given CIDR block, e.g. `10.0.0.0/8`
+    pub fn cidr(mut self, cidr: &str) -> Self {
+        self.cidr = Some(cidr.to_owned());
+        self
+    }
+
+    /// The address family to detect, defaults to IPv4
+    pub fn ip_family(mut self, ip_family: IpFamily) -> Self {
+        self.ip_family = ip_family;
+        self
+    }
+
+    /// Use the IP address rather than the host name when deriving urls
+    pub fn prefer_ip_address(mut self, prefer_ip_address: bool) -> Self {
+        self.prefer_ip_address = prefer_ip_address;
+        self
+    }
+
+    /// Defaults to the app name
+    pub fn vip_address(mut self, vip_address: &str) -> Self {
+        self.vip_address = Some(vip_address.to_owned());
+        self
+    }
+
+    /// Defaults to the app name
+    pub fn secure_vip_address(mut self, secure_vip_address: &str) -> Self {
+        self.secure_vip_address = Some(secure_vip_address.to_owned());
+        self
+    }
+
+    /// Defaults to `Status::Up`
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn secure_port(mut self, secure_port: u16) -> Self {
+        self.secure_port = Some(secure_port);
+        self
+    }
+
+    /// Defaults to `/`
+    pub fn home_page_path(mut self, path: &str) -> Self {
+        self.home_page_path = path.to_owned();
+        self
+    }
+
+    /// Defaults to `/status`
+    pub fn status_page_path(mut self, path: &str) -> Self {
+        self.status_page_path = path.to_owned();
+        self
+    }
+
+    /// Defaults to `/health`
+    pub fn health_check_path(mut self, path: &str) -> Self {
+        self.health_check_path = path.to_owned();
+        self
+    }
+
+    /// Defaults to `DcName::MyOwn` without metadata
+    pub fn data_center_info(mut self, data_center_info: DataCenterInfo) -> Self {
+        self.data_center_info = Some(data_center_info);
+        self
+    }
+
+    pub fn lease_info(mut self, lease_info: LeaseInfo) -> Self {
+        self.lease_info = Some(lease_info);
+        self
+    }
+
+    /// Adds a single metadata entry
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata
+            .insert(key.to_owned(), Value::String(value.to_owned()));
+        self
+    }
+
+    /// Builds the instance, detecting anything that was not explicitly supplied.
+    /// Only the app name and ports are checked: the instance is not validated, use
+    /// [build_validated](#method.build_validated) to catch what Eureka would reject.
+    pub fn build(self) -> Result<Instance, InstanceBuilderError> {
+        if self.app.trim().is_empty() {
+            return Err(InstanceBuilderError::MissingApp);
+        }
+        if self.port.is_none() && self.secure_port.is_none() {
+            return Err(InstanceBuilderError::MissingPort);
+        }
+
+        let host_name = match self.host_name {
+            Some(ref h) => h.clone(),
+            None => detect_host_name()?,
+        };
+        let ip_addr = match self.ip_addr {
+            Some(ip) => ip,
+            None => {
+                let cidr = match self.cidr {
+                    Some(ref c) => Some(Cidr::from_str(c)?),
+                    None => None,
+                };
+                let interfaces = if_addrs::get_if_addrs()
+                    .map_err(InstanceBuilderError::InterfaceLookup)?
+                    .into_iter()
+                    .map(|i| (i.name.clone(), i.ip()))
+                    .collect::<Vec<_>>();
+                select_address(
+                    &interfaces,
+                    self.interface.as_deref(),
+                    cidr.as_ref(),
+                    self.ip_family,
+                )?
+            }
+        };
+
+        let url_host = if self.prefer_ip_address {
+            match ip_addr {
+                IpAddr::V6(v6) => format!("[{}]", v6),
+                IpAddr::V4(v4) => v4.to_string(),
+            }
+        } else {
+            host_name.clone()
+        };
+        // prefer the plain port for the derived urls, as eureka itself does
+        let base_url = match (self.port, self.secure_port) {
+            (Some(p), _) => format!("http://{}:{}", url_host, p),
+            (None, Some(p)) => format!("https://{}:{}", url_host, p),
+            (None, None) => return Err(InstanceBuilderError::MissingPort),
+        };
+
+        let vip_address = self.vip_address.unwrap_or_else(|| self.app.clone());
+        let secure_vip_address = self.secure_vip_address.unwrap_or_else(|| self.app.clone());
+
+        Ok(Instance {
+            host_name,
+            app: self.app,
+            ip_addr: ip_addr.to_string(),
+            vip_address,
+            secure_vip_address,
+            status: self.status,
+            port: self.port,
+            secure_port: self.secure_port,
+            homepage_url: join_path(&base_url, &self.home_page_path),
+            status_page_url: join_path(&base_url, &self.status_page_path),
+            health_check_url: join_path(&base_url, &self.health_check_path),
+            data_center_info: self.data_center_info.unwrap_or(DataCenterInfo {
+                name: DcName::MyOwn,
+                metadata: None,
+                custom_metadata: None,
+            }),
+            lease_info: self.lease_info,
+            metadata: self.metadata,
+        })
+    }
+
+    /// Builds the instance and validates it, see
+    /// [Instance::validate](struct.Instance.html#method.validate)
+    pub fn build_validated(self) -> Result<Instance, InstanceBuilderError> {
+        let instance = self.build()?;
+        instance.validate().map_err(InstanceBuilderError::Invalid)?;
+        Ok(instance)
+    }
+}
+
+fn detect_host_name() -> Result<String, InstanceBuilderError> {
+    gethostname::gethostname()
+        .into_string()
+        .ok()
+        .filter(|h| !h.is_empty())
+        .ok_or(InstanceBuilderError::HostNameUnavailable)
+}
+
+fn join_path(base_url: &str, path: &str) -> String {
+    if path.starts_with('/') {
+        format!("{}{}", base_url, path)
+    } else {
+        format!("{}/{}", base_url, path)
+    }
+}
+
+/// Picks the first usable address from a list of (interface name, address) pairs
+fn select_address(
+    interfaces: &[(String, IpAddr)],
+    interface: Option<&str>,
+    cidr: Option<&Cidr>,
+    ip_family: IpFamily,
+) -> Result<IpAddr, InstanceBuilderError> {
+    interfaces
+        .iter()
+        .filter(|(name, _)| interface.is_none_or(|i| i == name))
+        .map(|(_, ip)| *ip)
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified() && !is_link_local(ip))
+        .filter(|ip| match ip_family {
+            IpFamily::V4 => ip.is_ipv4(),
+            IpFamily::V6 => ip.is_ipv6(),
+            IpFamily::Any => true,
+        })
+        .find(|ip| cidr.is_none_or(|c| c.contains(ip)))
+        .ok_or(InstanceBuilderError::NoMatchingAddress)
+}
+
+fn is_link_local(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = InstanceBuilderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InstanceBuilderError::InvalidCidr(s.to_owned());
+        let (addr, len) = s.split_once('/').ok_or_else(invalid)?;
+        let network = IpAddr::from_str(addr).map_err(|_| invalid())?;
+        let prefix_len = u8::from_str(len).map_err(|_| invalid())?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+        Ok(Cidr {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn sample_interfaces() -> Vec<(String, IpAddr)> {
+        vec![
+            ("lo".to_owned(), IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            ("lo".to_owned(), IpAddr::V6(Ipv6Addr::LOCALHOST)),
+            (
+                "eth0".to_owned(),
+                IpAddr::V6(Ipv6Addr::from_str("fe80::1").expect("test unwrap")),
+            ),
+            (
+                "eth0".to_owned(),
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)),
+            ),
+            ("eth1".to_owned(), IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3))),
+            (
+                "eth1".to_owned(),
+                IpAddr::V6(Ipv6Addr::from_str("2001:db8::5").expect("test unwrap")),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_select_address_skips_loopback() {
+        let ip = select_address(&sample_interfaces(), None, None, IpFamily::V4)
+            .expect("an address should be selected");
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)), ip);
+    }
+
+    #[test]
+    fn test_select_address_by_interface_and_cidr() {
+        let ip = select_address(&sample_interfaces(), Some("eth1"), None, IpFamily::V4)
+            .expect("an address should be selected");
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)), ip);
+
+        let cidr = Cidr::from_str("10.0.0.0/8").expect("cidr should parse");
+        let ip = select_address(&sample_interfaces(), None, Some(&cidr), IpFamily::V4)
+            .expect("an address should be selected");
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)), ip);
+    }
+
+    #[test]
+    fn test_select_address_ipv6_skips_link_local() {
+        let ip = select_address(&sample_interfaces(), None, None, IpFamily::V6)
+            .expect("an address should be selected");
+        assert_eq!(
+            IpAddr::V6(Ipv6Addr::from_str("2001:db8::5").expect("test unwrap")),
+            ip
+        );
+    }
+
+    #[test]
+    fn test_select_address_no_match() {
+        let cidr = Cidr::from_str("172.16.0.0/12").expect("cidr should parse");
+        let result = select_address(&sample_interfaces(), None, Some(&cidr), IpFamily::V4);
+        assert!(matches!(
+            result,
+            Err(InstanceBuilderError::NoMatchingAddress)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_cidr() {
+        assert!(Cidr::from_str("10.0.0.0").is_err());
+        assert!(Cidr::from_str("10.0.0.0/33").is_err());
+        assert!(Cidr::from_str("nope/8").is_err());
+    }
+
+    #[test]
+    fn test_build_derives_urls_and_vips() {
+        let instance = InstanceBuilder::new("MY_SERVICE")
+            .host_name("myhost")
+            .ip_addr(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)))
+            .port(8080)
+            .health_check_path("actuator/health")
+            .build()
+            .expect("instance should build");
+
+        assert_eq!("myhost", instance.host_name);
+        assert_eq!("10.1.2.3", instance.ip_addr);
+        assert_eq!("MY_SERVICE", instance.vip_address);
+        assert_eq!("MY_SERVICE", instance.secure_vip_address);
+        assert_eq!("http://myhost:8080/", instance.homepage_url);
+        assert_eq!("http://myhost:8080/status", instance.status_page_url);
+        assert_eq!(
+            "http://myhost:8080/actuator/health",
+            instance.health_check_url
+        );
+    }
+
+    #[test]
+    fn test_build_prefer_ip_address() {
+        let instance = InstanceBuilder::new("MY_SERVICE")
+            .host_name("myhost")
+            .ip_addr(IpAddr::V6(
+                Ipv6Addr::from_str("2001:db8::5").expect("test unwrap"),
+            ))
+            .secure_port(8443)
+            .prefer_ip_address(true)
+            .build()
+            .expect("instance should build");
+        assert_eq!("https://[2001:db8::5]:8443/", instance.homepage_url);
+    }
+
+    #[test]
+    fn test_build_requires_port() {
+        let result = InstanceBuilder::new("MY_SERVICE")
+            .host_name("myhost")
+            .ip_addr(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)))
+            .build();
+        assert!(matches!(result, Err(InstanceBuilderError::MissingPort)));
+    }
+
+    #[test]
+    fn test_build_validated() {
+        let builder = || {
+            InstanceBuilder::new("MY_SERVICE")
+                .host_name("myhost")
+                .ip_addr(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)))
+                .port(8080)
+                .data_center_info(DataCenterInfo {
+                    name: DcName::Amazon,
+                    metadata: None,
+                    custom_metadata: None,
+                })
+        };
+        // building alone does not validate
+        assert!(builder().build().is_ok());
+        match builder().build_validated() {
+            Err(InstanceBuilderError::Invalid(errors)) => {
+                assert_eq!(vec![ValidationError::MissingAmazonMetaData], errors)
+            }
+            other => panic!("expected validation failure, got {:?}", other),
+        }
+    }
+}
@@ -8,16 +8,21 @@ use std::str::FromStr;
 
 const MY_OWN: &str = "MyOwn";
 const AMAZON: &str = "Amazon";
+const NETFLIX: &str = "Netflix";
 
 #[derive(Debug, PartialEq)]
 pub enum DcName {
     MyOwn,
     Amazon,
+    Netflix,
+    /// Any non-standard data center name, e.g. for GCP or on-prem fleets
+    Custom(String),
 }
 
 impl DcName {
+    /// The standard data center names defined by Eureka
     pub fn values() -> Vec<DcName> {
-        vec![DcName::MyOwn, DcName::Amazon]
+        vec![DcName::MyOwn, DcName::Amazon, DcName::Netflix]
     }
 }
 
@@ -51,9 +56,11 @@ impl FromStr for DcName {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "MyOwn" => Ok(DcName::MyOwn),
-            "Amazon" => Ok(DcName::Amazon),
-            _ => Err(InvalidDcNameError::new(s)),
+            MY_OWN => Ok(DcName::MyOwn),
+            AMAZON => Ok(DcName::Amazon),
+            NETFLIX => Ok(DcName::Netflix),
+            _ if s.trim().is_empty() => Err(InvalidDcNameError::new(s)),
+            _ => Ok(DcName::Custom(s.to_owned())),
         }
     }
 }
@@ -63,15 +70,19 @@ impl From<DcName> for String {
         match s {
             DcName::MyOwn => MY_OWN.to_owned(),
             DcName::Amazon => AMAZON.to_owned(),
+            DcName::Netflix => NETFLIX.to_owned(),
+            DcName::Custom(name) => name,
         }
     }
 }
 
 impl From<&DcName> for String {
     fn from(s: &DcName) -> Self {
-        match *s {
+        match s {
             DcName::MyOwn => MY_OWN.to_owned(),
             DcName::Amazon => AMAZON.to_owned(),
+            DcName::Netflix => NETFLIX.to_owned(),
+            DcName::Custom(name) => name.clone(),
         }
     }
 }
@@ -132,15 +143,31 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_from_str_custom() {
+        assert_eq!(
+            DcName::Netflix,
+            DcName::from_str(NETFLIX).expect("should parse Netflix")
+        );
+        assert_eq!(
+            DcName::Custom("GCP".to_owned()),
+            DcName::from_str("GCP").expect("should parse a custom name")
+        );
+    }
+
     #[test]
     #[should_panic]
     fn test_from_str_invalid() {
-        DcName::from_str("sfd2ef").expect("should panic on invalid DcName");
+        DcName::from_str("").expect("should panic on an empty DcName");
     }
 
     #[test]
     fn test_to_string() {
         assert_eq!(AMAZON.to_owned(), String::from(DcName::Amazon));
         assert_eq!(MY_OWN.to_owned(), String::from(DcName::MyOwn));
+        assert_eq!(
+            "OnPrem".to_owned(),
+            String::from(DcName::Custom("OnPrem".to_owned()))
+        );
     }
 }
@@ -511,7 +511,7 @@ pub mod tests {
            "homePageUrl": "http://google.com",
            "statusPageUrl": "http://nytimes.com",
            "healthCheckUrl": "http://washingtonpost.com",
-           "dataCenterInfo": { "@class": "com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo", "name":"Amazon","metadata":
+           "dataCenterInfo": { "@class": "com.netflix.appinfo.AmazonInfo", "name":"Amazon","metadata":
            {
                 "ami-launch-index": "001a",
                 "local-hostname": "localhost0",
@@ -555,18 +555,20 @@ pub mod tests {
             data_center_info: DataCenterInfo {
                 name: DcName::Amazon,
                 metadata: Some(AmazonMetaData {
-                    ami_launch_index: "001a".to_string(),
+                    ami_launch_index: Some("001a".to_string()),
                     local_hostname: "localhost0".to_string(),
                     availability_zone: "US_East1a".to_string(),
                     instance_id: "instance1a".to_string(),
-                    public_ip4: "32.23.21.212".to_string(),
-                    public_hostname: "foo.coma".to_string(),
-                    ami_manifest_path: "/dev/nulla".to_string(),
+                    public_ip4: Some("32.23.21.212".to_string()),
+                    public_hostname: Some("foo.coma".to_string()),
+                    ami_manifest_path: Some("/dev/nulla".to_string()),
                     local_ip4: "127.0.0.12".to_string(),
-                    hostname: "privatefoo.coma".to_string(),
+                    hostname: Some("privatefoo.coma".to_string()),
                     ami_id: "ami0023".to_string(),
                     instance_type: "c4xlarged".to_string(),
+                    ..Default::default()
                 }),
+                custom_metadata: None,
             },
             lease_info: Some(LeaseInfo {
                 eviction_duration_in_secs: Some(9600),
@@ -1,15 +1,21 @@
 mod amazonmetadata;
+mod builder;
 mod datacenterinfo;
 mod dcname;
 mod instance;
 mod leaseinfo;
 mod register;
 mod status;
+mod validation;
 
 pub use self::amazonmetadata::AmazonMetaData;
+pub use self::builder::{InstanceBuilder, InstanceBuilderError, IpFamily};
 pub use self::datacenterinfo::DataCenterInfo;
 pub use self::dcname::DcName;
 pub use self::instance::Instance;
 pub use self::leaseinfo::LeaseInfo;
 pub use self::register::RegisterRequest;
 pub use self::status::Status;
+pub use self::validation::{ValidationError, MAX_METADATA_SIZE};
+
+pub(crate) use self::validation::join_errors;
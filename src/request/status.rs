@@ -10,7 +10,7 @@ const STARTING: &str = "STARTING";
 const OUT_OF_SERVICE: &str = "OUT_OF_SERVICE";
 const UNKNOWN: &str = "UNKNOWN";
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Status {
     Up,
     Down,
@@ -0,0 +1,178 @@
+use super::DcName;
+use super::Instance;
+use super::RegisterRequest;
+use std::error::Error;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use url::Url;
+
+/// The largest serialized size, in bytes, accepted for an instance's metadata map
+pub const MAX_METADATA_SIZE: usize = 16 * 1024;
+
+/// A single problem found while validating a registration
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    /// The app name is empty
+    EmptyApp,
+    /// The IP address could not be parsed as an `IpAddr`
+    InvalidIpAddr(String),
+    /// One of the instance urls could not be parsed
+    InvalidUrl {
+        field: &'static str,
+        value: String,
+        error: url::ParseError,
+    },
+    /// A secure port is set but the secure VIP address is empty
+    MissingSecureVipAddress,
+    /// The serialized metadata is larger than `MAX_METADATA_SIZE`
+    MetadataTooLarge(usize),
+    /// The data center is `DcName::Amazon` but no `AmazonMetaData` was supplied
+    MissingAmazonMetaData,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::EmptyApp => write!(f, "app must not be empty"),
+            ValidationError::InvalidIpAddr(ip) => write!(f, "invalid ipAddr: {:?}", ip),
+            ValidationError::InvalidUrl {
+                field,
+                value,
+                error,
+            } => write!(f, "invalid {}: {:?} ({})", field, value, error),
+            ValidationError::MissingSecureVipAddress => {
+                write!(f, "securePort is set but secureVipAddress is empty")
+            }
+            ValidationError::MetadataTooLarge(size) => write!(
+                f,
+                "metadata is {} bytes, the maximum is {}",
+                size, MAX_METADATA_SIZE
+            ),
+            ValidationError::MissingAmazonMetaData => {
+                write!(f, "dataCenterInfo is Amazon but has no metadata")
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+pub(crate) fn join_errors(errors: &[ValidationError]) -> String {
+    errors
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Instance {
+    /// Checks the instance for problems Eureka would reject with a bare 400,
+    /// returning every problem found
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+
+        if self.app.trim().is_empty() {
+            errors.push(ValidationError::EmptyApp);
+        }
+        if IpAddr::from_str(&self.ip_addr).is_err() {
+            errors.push(ValidationError::InvalidIpAddr(self.ip_addr.clone()));
+        }
+
+        let urls = [
+            ("homePageUrl", &self.homepage_url),
+            ("statusPageUrl", &self.status_page_url),
+            ("healthCheckUrl", &self.health_check_url),
+        ];
+        for (field, value) in urls {
+            if let Err(error) = Url::parse(value) {
+                errors.push(ValidationError::InvalidUrl {
+                    field,
+                    value: value.clone(),
+                    error,
+                });
+            }
+        }
+
+        if self.secure_port.is_some() && self.secure_vip_address.trim().is_empty() {
+            errors.push(ValidationError::MissingSecureVipAddress);
+        }
+
+        let metadata_size = serde_json::to_vec(&self.metadata)
+            .map(|v| v.len())
+            .unwrap_or(0);
+        if metadata_size > MAX_METADATA_SIZE {
+            errors.push(ValidationError::MetadataTooLarge(metadata_size));
+        }
+
+        if self.data_center_info.name == DcName::Amazon && self.data_center_info.metadata.is_none()
+        {
+            errors.push(ValidationError::MissingAmazonMetaData);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl RegisterRequest {
+    /// Validates the wrapped instance, see [Instance::validate](struct.Instance.html#method.validate)
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        self.instance.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::instance::tests::build_test_instance;
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_valid_instance() {
+        let instance = build_test_instance();
+        assert_eq!(Ok(()), instance.validate());
+        assert_eq!(Ok(()), RegisterRequest::new(instance).validate());
+    }
+
+    #[test]
+    fn test_collects_every_problem() {
+        let mut instance = build_test_instance();
+        instance.app = " ".to_owned();
+        instance.ip_addr = "not-an-ip".to_owned();
+        instance.status_page_url = "/status".to_owned();
+        instance.secure_vip_address = String::new();
+        instance.data_center_info.metadata = None;
+
+        let errors = instance.validate().expect_err("validation should fail");
+        assert_eq!(5, errors.len());
+        assert_eq!(ValidationError::EmptyApp, errors[0]);
+        assert_eq!(
+            ValidationError::InvalidIpAddr("not-an-ip".to_owned()),
+            errors[1]
+        );
+        assert!(matches!(
+            errors[2],
+            ValidationError::InvalidUrl {
+                field: "statusPageUrl",
+                ..
+            }
+        ));
+        assert_eq!(ValidationError::MissingSecureVipAddress, errors[3]);
+        assert_eq!(ValidationError::MissingAmazonMetaData, errors[4]);
+    }
+
+    #[test]
+    fn test_metadata_too_large() {
+        let mut instance = build_test_instance();
+        instance.metadata.insert(
+            "big".to_owned(),
+            Value::String("x".repeat(MAX_METADATA_SIZE)),
+        );
+        let errors = instance.validate().expect_err("validation should fail");
+        assert!(matches!(errors[0], ValidationError::MetadataTooLarge(_)));
+    }
+}
@@ -9,7 +9,7 @@ const ADDED: &str = "ADDED";
 const DELETED: &str = "DELETED";
 const MODIFIED: &str = "MODIFIED";
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ActionType {
     Added,
     Deleted,
@@ -1,19 +1,36 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct AmazonMetaData {
-    pub ami_launch_index: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ami_launch_index: Option<String>,
     pub local_hostname: String,
     pub availability_zone: String,
     pub instance_id: String,
-    pub public_ipv4: String,
-    pub public_hostname: String,
-    pub ami_manifest_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_ipv4: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_hostname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ami_manifest_path: Option<String>,
     pub local_ipv4: String,
-    pub hostname: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
     pub ami_id: String,
     pub instance_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vpc_id: Option<String>,
+    #[serde(rename = "accountId", default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    /// Any keys not listed above, kept as-is
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
 }
 
 #[cfg(test)]
@@ -24,17 +41,18 @@ pub mod tests {
     #[test]
     fn test_serialize_amazon_meta_data() {
         let md = AmazonMetaData {
-            ami_launch_index: "001a".to_string(),
+            ami_launch_index: Some("001a".to_string()),
             local_hostname: "localhost0".to_string(),
             availability_zone: "US_East1a".to_string(),
             instance_id: "instance1a".to_string(),
-            public_ipv4: "32.23.21.212".to_string(),
-            public_hostname: "foo.coma".to_string(),
-            ami_manifest_path: "/dev/nulla".to_string(),
+            public_ipv4: Some("32.23.21.212".to_string()),
+            public_hostname: Some("foo.coma".to_string()),
+            ami_manifest_path: Some("/dev/nulla".to_string()),
             local_ipv4: "127.0.0.12".to_string(),
-            hostname: "privatefoo.coma".to_string(),
+            hostname: Some("privatefoo.coma".to_string()),
             ami_id: "ami0023".to_string(),
             instance_type: "c4xlarged".to_string(),
+            ..Default::default()
         };
         let json = sample_meta_data();
 
@@ -45,23 +63,54 @@ pub mod tests {
     #[test]
     fn test_deserialize_amazon_meta_data() {
         let md = AmazonMetaData {
-            ami_launch_index: "001a".to_string(),
+            ami_launch_index: Some("001a".to_string()),
             local_hostname: "localhost0".to_string(),
             availability_zone: "US_East1a".to_string(),
             instance_id: "instance1a".to_string(),
-            public_ipv4: "32.23.21.212".to_string(),
-            public_hostname: "foo.coma".to_string(),
-            ami_manifest_path: "/dev/nulla".to_string(),
+            public_ipv4: Some("32.23.21.212".to_string()),
+            public_hostname: Some("foo.coma".to_string()),
+            ami_manifest_path: Some("/dev/nulla".to_string()),
             local_ipv4: "127.0.0.12".to_string(),
-            hostname: "privatefoo.coma".to_string(),
+            hostname: Some("privatefoo.coma".to_string()),
             ami_id: "ami0023".to_string(),
             instance_type: "c4xlarged".to_string(),
+            ..Default::default()
         };
         let json = sample_meta_data();
         let result = serde_json::from_str(&json).expect("deserialization should succeed");
         assert_eq!(md, result);
     }
 
+    #[test]
+    fn test_deserialize_vpc_only_meta_data() {
+        let json = r#"{
+            "local-hostname": "ip-10-0-0-12.ec2.internal",
+            "availability-zone": "us-east-1a",
+            "instance-id": "i-0abc",
+            "local-ipv4": "10.0.0.12",
+            "ami-id": "ami-0023",
+            "instance-type": "m5.large",
+            "mac": "0e:11:22:33:44:55",
+            "vpc-id": "vpc-123",
+            "accountId": "123456789012",
+            "kernel-id": "aki-1"
+        }"#;
+        let result: AmazonMetaData =
+            serde_json::from_str(json).expect("deserialization should succeed");
+        assert_eq!(None, result.public_ipv4);
+        assert_eq!(Some("vpc-123".to_owned()), result.vpc_id);
+        assert_eq!(Some("123456789012".to_owned()), result.account_id);
+        assert_eq!(
+            Some(&Value::String("aki-1".to_owned())),
+            result.other.get("kernel-id")
+        );
+
+        let round_trip = serde_json::to_string(&result).expect("serialization should succeed");
+        assert!(round_trip.contains("\"accountId\":\"123456789012\""));
+        assert!(round_trip.contains("\"kernel-id\":\"aki-1\""));
+        assert!(!round_trip.contains("public-ipv4"));
+    }
+
     pub fn sample_meta_data() -> String {
         r#"{ "ami-launch-index": "001a",
             "local-hostname": "localhost0",
@@ -1,9 +1,11 @@
 use super::Instance;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Application {
-    pub name: String,
+    #[serde(deserialize_with = "super::interned::deserialize")]
+    pub name: Arc<str>,
     // server returns instance as an array
     pub instance: Vec<Instance>,
 }
@@ -20,7 +22,7 @@ mod tests {
         let instance = build_test_instance();
         let name = "test_name";
         let app = Application {
-            name: name.to_owned(),
+            name: name.into(),
             instance: vec![instance],
         };
         let result = serde_json::to_string(&app).expect("serialization should succeed");
@@ -39,7 +41,7 @@ mod tests {
         let instance = build_test_instance();
         let name = "test_name";
         let app = Application {
-            name: name.to_owned(),
+            name: name.into(),
             instance: vec![instance],
         };
         let result = serde_json::from_str(&json).expect("deserialization should succeed");
@@ -1,7 +1,7 @@
 use super::Application;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ApplicationResponse {
     pub application: Application,
 }
@@ -23,7 +23,7 @@ mod tests {
         let json = build_application_response_json();
         let instance = build_test_instance();
         let application: Application = Application {
-            name: "test_app".to_owned(),
+            name: "test_app".into(),
             instance: vec![instance],
         };
         let ar = ApplicationResponse::new(application);
@@ -42,7 +42,7 @@ mod tests {
         let json = build_application_response_json();
         let instance = build_test_instance();
         let application: Application = Application {
-            name: "test_app".to_owned(),
+            name: "test_app".into(),
             instance: vec![instance],
         };
         let ar = ApplicationResponse::new(application);
@@ -1,15 +1,91 @@
-use super::Application;
+use super::{ActionType, Application, Instance};
 use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::collections::BTreeMap;
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Applications {
     pub versions_delta: i16,
     pub apps_hashcode: String,
     pub applications: Vec<Application>,
 }
 
+impl Applications {
+    /// Applies delta changes, as returned by the delta endpoint, following Eureka's
+    /// semantics: `ADDED` and `MODIFIED` instances replace the instance with the same id
+    /// or are appended to their app, `DELETED` ones are removed, and applications left
+    /// without instances are dropped.
+    ///
+    /// Returns the updated snapshot with its [reconcile hashcode](#method.reconcile_hashcode);
+    /// `versions_delta` is kept as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - The instances of the delta, each tagged with its `actionType`
+    pub fn apply_delta<'a, I>(&self, changes: I) -> Applications
+    where
+        I: IntoIterator<Item = &'a Instance>,
+    {
+        let mut applications = self.applications.clone();
+        for change in changes {
+            let app = change.app.to_ascii_uppercase();
+            let id = change.id();
+            let existing = applications
+                .iter()
+                .enumerate()
+                .find_map(|(a, application)| {
+                    application
+                        .instance
+                        .iter()
+                        .position(|i| i.id() == id)
+                        .filter(|_| application.name.eq_ignore_ascii_case(&app))
+                        .map(|i| (a, i))
+                });
+            match (&change.action_type, existing) {
+                (ActionType::Deleted, Some((a, i))) => {
+                    applications[a].instance.remove(i);
+                }
+                (ActionType::Deleted, None) => {}
+                (_, Some((a, i))) => applications[a].instance[i] = change.clone(),
+                (_, None) => match applications
+                    .iter_mut()
+                    .find(|a| a.name.eq_ignore_ascii_case(&app))
+                {
+                    Some(application) => application.instance.push(change.clone()),
+                    None => applications.push(Application {
+                        name: change.app.clone(),
+                        instance: vec![change.clone()],
+                    }),
+                },
+            }
+        }
+        applications.retain(|a| !a.instance.is_empty());
+
+        let mut updated = Applications {
+            versions_delta: self.versions_delta,
+            apps_hashcode: String::new(),
+            applications,
+        };
+        updated.apps_hashcode = updated.reconcile_hashcode();
+        updated
+    }
+
+    /// The hashcode Eureka uses to check a client's registry is in sync, in the format of
+    /// Java's `getReconcileHashCode`: the number of instances in each status, sorted by
+    /// status, e.g. `DOWN_1_UP_2_`
+    pub fn reconcile_hashcode(&self) -> String {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for instance in self.applications.iter().flat_map(|a| a.instance.iter()) {
+            *counts.entry(String::from(&instance.status)).or_default() += 1;
+        }
+        counts
+            .iter()
+            .map(|(status, count)| format!("{}_{}_", status, count))
+            .collect()
+    }
+}
+
 // Custom deserializer to handle both single Application object and array of Applications
 fn deserialize_application_field<'de, D>(deserializer: D) -> Result<Vec<Application>, D::Error>
 where
@@ -172,6 +248,7 @@ impl From<Application> for Vec<Application> {
 
 #[cfg(test)]
 pub mod tests {
+    use super::super::instance::tests::build_test_instance;
     use super::super::ActionType;
     use super::super::Application;
     use super::super::DataCenterInfo;
@@ -206,15 +283,79 @@ pub mod tests {
         assert_eq!(2, result.applications.len())
     }
 
+    fn instance(app: &str, host: &str, status: Status, action_type: ActionType) -> Instance {
+        let mut instance = build_test_instance();
+        instance.app = app.into();
+        instance.host_name = host.to_owned();
+        instance.status = status;
+        instance.action_type = action_type;
+        instance
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let applications = build_test_applications();
+        let delta = vec![
+            instance("BAR", "bar-1", Status::Up, ActionType::Added),
+            instance("bar", "bar-2", Status::Starting, ActionType::Added),
+            instance("BAR", "bar-1", Status::Down, ActionType::Modified),
+            instance(
+                "INTEGRATION_TEST",
+                "localhost",
+                Status::Up,
+                ActionType::Deleted,
+            ),
+            instance("MISSING", "gone", Status::Up, ActionType::Deleted),
+        ];
+        let updated = applications.apply_delta(&delta);
+
+        assert_eq!(1, updated.applications.len());
+        let bar = &updated.applications[0];
+        assert_eq!("BAR", &*bar.name);
+        assert_eq!(
+            vec![("bar-1", Status::Down), ("bar-2", Status::Starting)],
+            bar.instance
+                .iter()
+                .map(|i| (i.host_name.as_str(), i.status.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("DOWN_1_STARTING_1_", updated.apps_hashcode);
+        assert_eq!(applications.versions_delta, updated.versions_delta);
+        // the snapshot itself is left untouched
+        assert_eq!(build_test_applications(), applications);
+    }
+
+    #[test]
+    fn test_reconcile_hashcode() {
+        let mut applications = build_test_applications();
+        assert_eq!("UP_1_", applications.reconcile_hashcode());
+
+        applications.applications.push(Application {
+            name: "BAR".into(),
+            instance: vec![
+                instance("BAR", "a", Status::Up, ActionType::Added),
+                instance("BAR", "b", Status::OutOfService, ActionType::Added),
+                instance("BAR", "c", Status::Down, ActionType::Added),
+            ],
+        });
+        assert_eq!(
+            "DOWN_1_OUT_OF_SERVICE_1_UP_2_",
+            applications.reconcile_hashcode()
+        );
+        applications.applications.clear();
+        assert_eq!("", applications.reconcile_hashcode());
+    }
+
     pub fn build_test_applications() -> Applications {
         Applications {
             versions_delta: 1,
             apps_hashcode: "UP_1_".to_string(),
             applications: vec![Application {
-                name: "INTEGRATION_TEST".to_string(),
+                name: "INTEGRATION_TEST".into(),
                 instance: vec![Instance {
+                    instance_id: None,
                     host_name: "localhost".to_string(),
-                    app: "INTEGRATION_TEST".to_string(),
+                    app: "INTEGRATION_TEST".into(),
                     ip_addr: "127.0.0.1".to_string(),
                     status: Status::Up,
                     overriddenstatus: Some(Status::Unknown),
@@ -224,6 +365,7 @@ pub mod tests {
                     data_center_info: DataCenterInfo {
                         name: DcName::MyOwn,
                         metadata: None,
+                        custom_metadata: None,
                     },
                     lease_info: Some(LeaseInfo {
                         renewal_interval_in_secs: 30,
@@ -237,9 +379,11 @@ pub mod tests {
                     homepage_url: "http://google.com".to_string(),
                     status_page_url: "http://google.com".to_string(),
                     health_check_url: "http://google.com".to_string(),
-                    vip_address: "127.0.0.1".to_string(),
-                    secure_vip_address: "127.0.0.1".to_string(),
+                    vip_address: "127.0.0.1".into(),
+                    secure_vip_address: "127.0.0.1".into(),
                     is_coordinating_discovery_server: false,
+                    region: None,
+                    cluster: None,
                     last_updated_timestamp: 1503701416750,
                     last_dirty_timestamp: 1503701416457,
                     action_type: ActionType::Added,
@@ -1,7 +1,7 @@
 use super::Applications;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ApplicationsResponse {
     pub applications: Applications,
 }
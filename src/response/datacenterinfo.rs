@@ -2,6 +2,7 @@ use super::AmazonMetaData;
 use super::DcName;
 use serde::de::{Deserialize, Deserializer, Error as DeError, MapAccess, Visitor};
 use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::{Map, Value};
 use std::fmt;
 
 // Field name constants
@@ -12,11 +13,29 @@ const CLASS: &str = "@class";
 const CLASS_VALUE: &str = "com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo";
 const DATA_CENTER_INFO: &str = "DataCenterInfo";
 const FIELDS: &[&str] = &[CLASS, NAME, METADATA];
+// custom metadata key holding the zone, as written by the GCP metadata provider
+const ZONE: &str = "zone";
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DataCenterInfo {
     pub name: DcName,
+    /// Only used when the name is `DcName::Amazon`
     pub metadata: Option<AmazonMetaData>,
+    /// Provider metadata for any other data center, e.g. GCP zone and project
+    pub custom_metadata: Option<Map<String, Value>>,
+}
+
+impl DataCenterInfo {
+    /// The availability zone, read from the Amazon metadata or the `zone` custom metadata key
+    pub fn zone(&self) -> Option<&str> {
+        match (&self.metadata, &self.custom_metadata) {
+            (Some(amazon), _) if !amazon.availability_zone.is_empty() => {
+                Some(&amazon.availability_zone)
+            }
+            (_, Some(custom)) => custom.get(ZONE).and_then(Value::as_str),
+            _ => None,
+        }
+    }
 }
 
 impl Serialize for DataCenterInfo {
@@ -28,7 +47,10 @@ impl Serialize for DataCenterInfo {
         // weird netflix field
         s.serialize_field(CLASS, CLASS_VALUE)?;
         s.serialize_field(NAME, &self.name)?;
-        s.serialize_field(METADATA, &self.metadata)?;
+        match (&self.metadata, &self.custom_metadata) {
+            (None, Some(custom)) => s.serialize_field(METADATA, custom)?,
+            (metadata, _) => s.serialize_field(METADATA, metadata)?,
+        }
         s.end()
     }
 }
@@ -87,8 +109,8 @@ impl<'de> Deserialize<'de> for DataCenterInfo {
                 A: MapAccess<'de>,
             {
                 let mut maybe_name = None;
-                let mut maybe_metadata = None;
-                let mut maybe_class: Option<&str> = None;
+                let mut maybe_metadata: Option<Value> = None;
+                let mut maybe_class: Option<String> = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -109,11 +131,30 @@ impl<'de> Deserialize<'de> for DataCenterInfo {
                         }
                     }
                 }
-                let name = maybe_name.ok_or_else(|| DeError::missing_field(NAME));
+                let name = maybe_name.ok_or_else(|| DeError::missing_field(NAME))?;
                 debug!("Found ignored field @class {:?} ?", maybe_class);
+
+                // the shape of the metadata depends on the data center name
+                let mut metadata = None;
+                let mut custom_metadata = None;
+                match maybe_metadata {
+                    None | Some(Value::Null) => {}
+                    Some(value) if name == DcName::Amazon => {
+                        metadata = Some(serde_json::from_value(value).map_err(DeError::custom)?);
+                    }
+                    Some(Value::Object(map)) => custom_metadata = Some(map),
+                    Some(other) => {
+                        return Err(DeError::custom(format!(
+                            "Expecting metadata to be an object, found {}",
+                            other
+                        )))
+                    }
+                }
+
                 Ok(DataCenterInfo {
-                    name: name?,
-                    metadata: maybe_metadata,
+                    name,
+                    metadata,
+                    custom_metadata,
                 })
             }
         }
@@ -132,18 +173,20 @@ pub mod test {
         let dci = DataCenterInfo {
             name: DcName::Amazon,
             metadata: Some(AmazonMetaData {
-                ami_launch_index: "001a".to_string(),
+                ami_launch_index: Some("001a".to_string()),
                 local_hostname: "localhost0".to_string(),
                 availability_zone: "US_East1a".to_string(),
                 instance_id: "instance1a".to_string(),
-                public_ipv4: "32.23.21.212".to_string(),
-                public_hostname: "foo.coma".to_string(),
-                ami_manifest_path: "/dev/nulla".to_string(),
+                public_ipv4: Some("32.23.21.212".to_string()),
+                public_hostname: Some("foo.coma".to_string()),
+                ami_manifest_path: Some("/dev/nulla".to_string()),
                 local_ipv4: "127.0.0.12".to_string(),
-                hostname: "privatefoo.coma".to_string(),
+                hostname: Some("privatefoo.coma".to_string()),
                 ami_id: "ami0023".to_string(),
                 instance_type: "c4xlarged".to_string(),
+                ..Default::default()
             }),
+            custom_metadata: None,
         };
         let json = sample_data_center();
         let result = serde_json::to_string(&dci).expect("serialization should succeed");
@@ -155,18 +198,20 @@ pub mod test {
         let dci = DataCenterInfo {
             name: DcName::Amazon,
             metadata: Some(AmazonMetaData {
-                ami_launch_index: "001a".to_string(),
+                ami_launch_index: Some("001a".to_string()),
                 local_hostname: "localhost0".to_string(),
                 availability_zone: "US_East1a".to_string(),
                 instance_id: "instance1a".to_string(),
-                public_ipv4: "32.23.21.212".to_string(),
-                public_hostname: "foo.coma".to_string(),
-                ami_manifest_path: "/dev/nulla".to_string(),
+                public_ipv4: Some("32.23.21.212".to_string()),
+                public_hostname: Some("foo.coma".to_string()),
+                ami_manifest_path: Some("/dev/nulla".to_string()),
                 local_ipv4: "127.0.0.12".to_string(),
-                hostname: "privatefoo.coma".to_string(),
+                hostname: Some("privatefoo.coma".to_string()),
                 ami_id: "ami0023".to_string(),
                 instance_type: "c4xlarged".to_string(),
+                ..Default::default()
             }),
+            custom_metadata: None,
         };
         let json = sample_data_center();
         println!("json {}", json);
@@ -174,6 +219,24 @@ pub mod test {
         assert_eq!(dci, result);
     }
 
+    #[test]
+    fn test_custom_data_center_info() {
+        let mut custom = Map::new();
+        custom.insert("zone".to_owned(), Value::String("us-central1-a".to_owned()));
+        let dci = DataCenterInfo {
+            name: DcName::Custom("GCP".to_owned()),
+            metadata: None,
+            custom_metadata: Some(custom),
+        };
+        let json = r#"{"@class":"com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo","name":"GCP","metadata":{"zone":"us-central1-a"}}"#;
+
+        let result = serde_json::to_string(&dci).expect("serialization should succeed");
+        assert_eq!(json, result);
+        let result: DataCenterInfo =
+            serde_json::from_str(json).expect("deserialization should succeed");
+        assert_eq!(dci, result);
+    }
+
     fn sample_data_center() -> String {
         format!("{{\"@class\":\"com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo\",\"name\":\"Amazon\",\"metadata\":{}}}", sample_meta_data())
     }
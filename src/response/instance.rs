@@ -1,3 +1,4 @@
+use super::interned::intern;
 use super::ActionType;
 use super::DataCenterInfo;
 use super::LeaseInfo;
@@ -6,9 +7,11 @@ use serde::de::{Deserialize, Deserializer, Error as DeError, MapAccess, Visitor}
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 use serde_json::{Map, Value};
 use std::fmt;
+use std::sync::Arc;
 
 // Field name constants
 const INSTANCE: &str = "Instance";
+const INSTANCE_ID: &str = "instanceId";
 const HOST_NAME: &str = "hostName";
 const APP: &str = "app";
 const IP_ADDR: &str = "ipAddr";
@@ -31,6 +34,7 @@ const ACTION_TYPE: &str = "actionType";
 const IS_COORDINATED_DISCOVERY_SERVER: &str = "isCoordinatingDiscoveryServer";
 const JSON_FIELDS: &[&str] = &[
     INSTANCE,
+    INSTANCE_ID,
     HOST_NAME,
     APP,
     IP_ADDR,
@@ -53,6 +57,7 @@ const JSON_FIELDS: &[&str] = &[
     IS_COORDINATED_DISCOVERY_SERVER,
 ];
 const RUST_FIELDS: &[&str] = &[
+    "instance_id",
     "host_name",
     "app",
     "ip_addr",
@@ -79,13 +84,14 @@ const PORT_DOLLAR: &str = "$";
 const PORT_ENABLED: &str = "@enabled";
 const PORT_FIELDS: &[&str] = &[PORT_DOLLAR, PORT_ENABLED];
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Instance {
+    pub instance_id: Option<String>,
     pub host_name: String,
-    pub app: String,
+    pub app: Arc<str>,
     pub ip_addr: String,
-    pub vip_address: String,
-    pub secure_vip_address: String,
+    pub vip_address: Arc<str>,
+    pub secure_vip_address: Arc<str>,
     pub status: Status,
     pub port: Option<u16>,
     pub secure_port: Option<u16>,
@@ -101,6 +107,37 @@ pub struct Instance {
     pub last_dirty_timestamp: i64,
     pub action_type: ActionType,
     pub is_coordinating_discovery_server: bool,
+    /// The region the instance was fetched from, set by
+    /// [RegionConfig::tag](../region/struct.RegionConfig.html#method.tag) and never serialized
+    pub region: Option<Arc<str>>,
+    /// The Eureka cluster the instance was fetched from, set by
+    /// [FederatedClient](../federation/struct.FederatedClient.html) and never serialized
+    pub cluster: Option<Arc<str>>,
+}
+
+impl Instance {
+    /// The id Eureka knows the instance by, which is the host name when no instance id is set
+    pub fn id(&self) -> &str {
+        self.instance_id.as_deref().unwrap_or(&self.host_name)
+    }
+
+    /// The availability zone, read from the data center info or else from the `zone`
+    /// metadata key, where Spring Cloud registers it
+    pub fn zone(&self) -> Option<&str> {
+        self.data_center_info
+            .zone()
+            .or_else(|| self.metadata.get("zone").and_then(Value::as_str))
+    }
+
+    /// The status callers should act on, following Eureka's override rules: an override set
+    /// through the status endpoint, e.g. `OUT_OF_SERVICE`, only applies to an instance
+    /// reporting itself `UP` or `OUT_OF_SERVICE`, and an `UNKNOWN` override is ignored
+    pub fn effective_status(&self) -> Status {
+        match (&self.status, &self.overriddenstatus) {
+            (Status::Up | Status::OutOfService, Some(s)) if *s != Status::Unknown => s.clone(),
+            _ => self.status.clone(),
+        }
+    }
 }
 
 struct Port {
@@ -220,6 +257,9 @@ impl Serialize for Instance {
         S: Serializer,
     {
         let mut s = serializer.serialize_struct(INSTANCE, 14)?;
+        if let Some(instance_id) = &self.instance_id {
+            s.serialize_field(INSTANCE_ID, instance_id)?;
+        }
         s.serialize_field(HOST_NAME, &self.host_name)?;
         s.serialize_field(APP, &self.app)?;
         s.serialize_field(IP_ADDR, &self.ip_addr)?;
@@ -274,6 +314,7 @@ impl<'de> Deserialize<'de> for Instance {
         D: Deserializer<'de>,
     {
         enum Field {
+            InstanceId,
             HostName,
             App,
             IpAddr,
@@ -314,6 +355,7 @@ impl<'de> Deserialize<'de> for Instance {
                         E: DeError,
                     {
                         match v {
+                            INSTANCE_ID => Ok(Field::InstanceId),
                             HOST_NAME => Ok(Field::HostName),
                             APP => Ok(Field::App),
                             IP_ADDR => Ok(Field::IpAddr),
@@ -359,6 +401,7 @@ impl<'de> Deserialize<'de> for Instance {
             where
                 A: MapAccess<'de>,
             {
+                let mut maybe_instance_id = None;
                 let mut maybe_host_name = None;
                 let mut maybe_app = None;
                 let mut maybe_ip_addr = None;
@@ -392,7 +435,7 @@ impl<'de> Deserialize<'de> for Instance {
                             if maybe_app.is_some() {
                                 return Err(DeError::duplicate_field(APP));
                             }
-                            maybe_app = Some(map.next_value()?);
+                            maybe_app = Some(intern(&map.next_value::<String>()?));
                         }
                         Field::IpAddr => {
                             if maybe_ip_addr.is_some() {
@@ -404,13 +447,13 @@ impl<'de> Deserialize<'de> for Instance {
                             if maybe_vip_address.is_some() {
                                 return Err(DeError::duplicate_field(VIP_ADDRESS));
                             }
-                            maybe_vip_address = Some(map.next_value()?);
+                            maybe_vip_address = Some(intern(&map.next_value::<String>()?));
                         }
                         Field::SecureVipAddress => {
                             if maybe_secure_vip_address.is_some() {
                                 return Err(DeError::duplicate_field(SECURE_VIP_ADDRESS));
                             }
-                            maybe_secure_vip_address = Some(map.next_value()?);
+                            maybe_secure_vip_address = Some(intern(&map.next_value::<String>()?));
                         }
                         Field::Status => {
                             if maybe_status.is_some() {
@@ -460,6 +503,12 @@ impl<'de> Deserialize<'de> for Instance {
                             }
                             maybe_metadata = Some(map.next_value()?);
                         }
+                        Field::InstanceId => {
+                            if maybe_instance_id.is_some() {
+                                return Err(DeError::duplicate_field(INSTANCE_ID));
+                            }
+                            maybe_instance_id = Some(map.next_value()?);
+                        }
                         Field::HostName => {
                             if maybe_host_name.is_some() {
                                 return Err(DeError::duplicate_field(HOST_NAME));
@@ -569,6 +618,7 @@ impl<'de> Deserialize<'de> for Instance {
                 let country_id = maybe_country_id.ok_or_else(|| DeError::missing_field(COUNTRY_ID));
 
                 Ok(Instance {
+                    instance_id: maybe_instance_id,
                     host_name: host_name?,
                     app: app?,
                     ip_addr: ip_addr?,
@@ -589,6 +639,8 @@ impl<'de> Deserialize<'de> for Instance {
                     last_dirty_timestamp: last_dirty_timestamp?,
                     is_coordinating_discovery_server: is_coordinating_discovery_server?,
                     action_type: action_type?,
+                    region: None,
+                    cluster: None,
                 })
             }
         }
@@ -625,6 +677,36 @@ pub mod tests {
         assert_eq!(instance, result);
     }
 
+    #[test]
+    fn test_effective_status() {
+        let mut instance = build_test_instance();
+        assert_eq!(Status::Up, instance.effective_status());
+
+        instance.overriddenstatus = Some(Status::OutOfService);
+        assert_eq!(Status::OutOfService, instance.effective_status());
+
+        // an instance reporting itself down, starting or unknown wins over the override
+        instance.status = Status::Down;
+        assert_eq!(Status::Down, instance.effective_status());
+        instance.status = Status::Starting;
+        assert_eq!(Status::Starting, instance.effective_status());
+
+        instance.status = Status::Unknown;
+        assert_eq!(Status::Unknown, instance.effective_status());
+
+        // an UNKNOWN override is no override
+        instance.status = Status::Up;
+        instance.overriddenstatus = Some(Status::Unknown);
+        assert_eq!(Status::Up, instance.effective_status());
+        instance.status = Status::OutOfService;
+        instance.overriddenstatus = Some(Status::Up);
+        assert_eq!(Status::Up, instance.effective_status());
+
+        instance.status = Status::Up;
+        instance.overriddenstatus = None;
+        assert_eq!(Status::Up, instance.effective_status());
+    }
+
     pub fn build_test_instance_json() -> String {
         r#"{
            "hostName": "Foo",
@@ -680,11 +762,12 @@ pub mod tests {
             Value::String("somethingelse".to_owned()),
         );
         Instance {
+            instance_id: None,
             host_name: "Foo".to_string(),
-            app: "Bar".to_string(),
+            app: "Bar".into(),
             ip_addr: "3.128.2.12".to_string(),
-            vip_address: "127.0.0.1".to_string(),
-            secure_vip_address: "127.0.0.2".to_string(),
+            vip_address: "127.0.0.1".into(),
+            secure_vip_address: "127.0.0.2".into(),
             status: Status::Up,
             port: Some(80),
             secure_port: Some(443),
@@ -694,18 +777,20 @@ pub mod tests {
             data_center_info: DataCenterInfo {
                 name: DcName::Amazon,
                 metadata: Some(AmazonMetaData {
-                    ami_launch_index: "001a".to_string(),
+                    ami_launch_index: Some("001a".to_string()),
                     local_hostname: "localhost0".to_string(),
                     availability_zone: "US_East1a".to_string(),
                     instance_id: "instance1a".to_string(),
-                    public_ipv4: "32.23.21.212".to_string(),
-                    public_hostname: "foo.coma".to_string(),
-                    ami_manifest_path: "/dev/nulla".to_string(),
+                    public_ipv4: Some("32.23.21.212".to_string()),
+                    public_hostname: Some("foo.coma".to_string()),
+                    ami_manifest_path: Some("/dev/nulla".to_string()),
                     local_ipv4: "127.0.0.12".to_string(),
-                    hostname: "privatefoo.coma".to_string(),
+                    hostname: Some("privatefoo.coma".to_string()),
                     ami_id: "ami0023".to_string(),
                     instance_type: "c4xlarged".to_string(),
+                    ..Default::default()
                 }),
+                custom_metadata: None,
             },
             lease_info: Some(LeaseInfo {
                 renewal_interval_in_secs: 30,
@@ -722,6 +807,8 @@ pub mod tests {
             last_updated_timestamp: 1503442035871,
             action_type: ActionType::Added,
             is_coordinating_discovery_server: false,
+            region: None,
+            cluster: None,
         }
     }
 
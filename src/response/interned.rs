@@ -0,0 +1,59 @@
+use serde::de::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// strings no snapshot refers to anymore are dropped once the set grows past this
+const PRUNE_THRESHOLD: usize = 1024;
+
+struct Interner {
+    strings: HashSet<Arc<str>>,
+    prune_at: usize,
+}
+
+/// Returns a shared copy of the string, so the app names and VIPs repeated across every
+/// instance of every snapshot are only allocated once
+pub fn intern(s: &str) -> Arc<str> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    let mut interner = INTERNER
+        .get_or_init(|| {
+            Mutex::new(Interner {
+                strings: HashSet::new(),
+                prune_at: PRUNE_THRESHOLD,
+            })
+        })
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if let Some(interned) = interner.strings.get(s) {
+        return interned.clone();
+    }
+    if interner.strings.len() >= interner.prune_at {
+        interner.strings.retain(|s| Arc::strong_count(s) > 1);
+        interner.prune_at = PRUNE_THRESHOLD.max(interner.strings.len() * 2);
+    }
+    let interned: Arc<str> = Arc::from(s);
+    interner.strings.insert(interned.clone());
+    interned
+}
+
+/// Deserializes a string field through [intern](fn.intern.html)
+pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Arc<str>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(intern(&s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_shares_strings() {
+        let a = intern("INVENTORY-SERVICE");
+        let b = intern("INVENTORY-SERVICE");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &intern("ORDER-SERVICE")));
+    }
+}
@@ -7,6 +7,7 @@ mod applications_response;
 mod datacenterinfo;
 mod dcname;
 mod instance;
+mod interned;
 mod leaseinfo;
 mod status;
 
@@ -19,5 +20,9 @@ pub use self::applications_response::ApplicationsResponse;
 pub use self::datacenterinfo::DataCenterInfo;
 pub use self::dcname::DcName;
 pub use self::instance::Instance;
+pub use self::interned::intern;
 pub use self::leaseinfo::LeaseInfo;
 pub use self::status::Status;
+
+#[cfg(test)]
+pub(crate) use self::instance::tests::build_test_instance;
@@ -0,0 +1,353 @@
+use crate::errors::EurekaClientError;
+use crate::load_balancer::instance_key;
+use crate::registry::CachedRegistry;
+use crate::request::RegisterRequest;
+use crate::response::Instance;
+use crate::static_registry::StaticRegistry;
+use crate::EurekaClient;
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Registers this instance with a discovery backend and keeps its lease alive
+#[async_trait]
+pub trait ServiceRegistry: Send + Sync {
+    /// Registers the instance under its `app`
+    async fn register(&self, registration: &RegisterRequest) -> Result<(), EurekaClientError>;
+
+    async fn deregister(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError>;
+
+    /// Renews the lease of a registered instance
+    async fn heartbeat(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError>;
+}
+
+/// Looks up the instances of a service in a discovery backend
+#[async_trait]
+pub trait ServiceDiscovery: Send + Sync {
+    /// The instances of the service, empty when the backend does not know it
+    async fn instances(&self, service: &str) -> Result<Vec<Instance>, EurekaClientError>;
+}
+
+#[async_trait]
+impl ServiceRegistry for EurekaClient {
+    async fn register(&self, registration: &RegisterRequest) -> Result<(), EurekaClientError> {
+        EurekaClient::register(self, &registration.instance.app, registration).await
+    }
+
+    async fn deregister(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError> {
+        EurekaClient::deregister(self, app, instance_id).await
+    }
+
+    async fn heartbeat(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError> {
+        EurekaClient::heartbeat(self, app, instance_id).await
+    }
+}
+
+#[async_trait]
+impl ServiceDiscovery for EurekaClient {
+    async fn instances(&self, service: &str) -> Result<Vec<Instance>, EurekaClientError> {
+        match self.get_application(service).await {
+            Ok(res) => Ok(res.application.instance),
+            Err(EurekaClientError::NotFound) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl ServiceDiscovery for CachedRegistry {
+    async fn instances(&self, service: &str) -> Result<Vec<Instance>, EurekaClientError> {
+        Ok(self
+            .resolve(service)
+            .await?
+            .map(|app| app.instance.clone())
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl ServiceDiscovery for StaticRegistry {
+    async fn instances(&self, service: &str) -> Result<Vec<Instance>, EurekaClientError> {
+        Ok(self
+            .get(service)
+            .map(|app| app.instance.clone())
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl<T: ServiceRegistry + ?Sized> ServiceRegistry for Arc<T> {
+    async fn register(&self, registration: &RegisterRequest) -> Result<(), EurekaClientError> {
+        (**self).register(registration).await
+    }
+
+    async fn deregister(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError> {
+        (**self).deregister(app, instance_id).await
+    }
+
+    async fn heartbeat(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError> {
+        (**self).heartbeat(app, instance_id).await
+    }
+}
+
+#[async_trait]
+impl<T: ServiceDiscovery + ?Sized> ServiceDiscovery for Arc<T> {
+    async fn instances(&self, service: &str) -> Result<Vec<Instance>, EurekaClientError> {
+        (**self).instances(service).await
+    }
+}
+
+/// Two backends used as one while migrating from the primary to the secondary.
+///
+/// Every registration, deregistration and heartbeat goes to both backends at once and
+/// succeeds when either of them accepts it; a failure of the other is logged. A heartbeat
+/// that either backend answers with `NotFound` fails with `NotFound`, so the caller
+/// registers the instance again. Discovery
+/// merges the instances of both, keeping the primary's copy of an instance found in both
+/// by host and port, and falls back to whichever backend answered.
+pub struct CompositeRegistry<P, S> {
+    primary: P,
+    secondary: S,
+}
+
+impl<P, S> CompositeRegistry<P, S> {
+    pub fn new(primary: P, secondary: S) -> CompositeRegistry<P, S> {
+        CompositeRegistry { primary, secondary }
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+}
+
+/// Keeps the primary's result, or the secondary's when only the primary failed
+fn either<T>(
+    operation: &str,
+    primary: Result<T, EurekaClientError>,
+    secondary: Result<T, EurekaClientError>,
+) -> Result<T, EurekaClientError> {
+    match (primary, secondary) {
+        (Ok(primary), Ok(_)) => Ok(primary),
+        (Ok(primary), Err(e)) => {
+            warn!("{} failed on the secondary registry: {}", operation, e);
+            Ok(primary)
+        }
+        (Err(e), Ok(secondary)) => {
+            warn!("{} failed on the primary registry: {}", operation, e);
+            Ok(secondary)
+        }
+        (Err(e), Err(_)) => Err(e),
+    }
+}
+
+#[async_trait]
+impl<P: ServiceRegistry, S: ServiceRegistry> ServiceRegistry for CompositeRegistry<P, S> {
+    async fn register(&self, registration: &RegisterRequest) -> Result<(), EurekaClientError> {
+        let (primary, secondary) = futures::join!(
+            self.primary.register(registration),
+            self.secondary.register(registration)
+        );
+        either("register", primary, secondary)
+    }
+
+    async fn deregister(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError> {
+        let (primary, secondary) = futures::join!(
+            self.primary.deregister(app, instance_id),
+            self.secondary.deregister(app, instance_id)
+        );
+        either("deregister", primary, secondary)
+    }
+
+    async fn heartbeat(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError> {
+        let (primary, secondary) = futures::join!(
+            self.primary.heartbeat(app, instance_id),
+            self.secondary.heartbeat(app, instance_id)
+        );
+        // an instance one backend forgot must be registered again on it
+        match (primary, secondary) {
+            (Err(EurekaClientError::NotFound), _) => {
+                warn!(
+                    "heartbeat found no instance {} on the primary registry",
+                    instance_id
+                );
+                Err(EurekaClientError::NotFound)
+            }
+            (_, Err(EurekaClientError::NotFound)) => {
+                warn!(
+                    "heartbeat found no instance {} on the secondary registry",
+                    instance_id
+                );
+                Err(EurekaClientError::NotFound)
+            }
+            (primary, secondary) => either("heartbeat", primary, secondary),
+        }
+    }
+}
+
+#[async_trait]
+impl<P: ServiceDiscovery, S: ServiceDiscovery> ServiceDiscovery for CompositeRegistry<P, S> {
+    async fn instances(&self, service: &str) -> Result<Vec<Instance>, EurekaClientError> {
+        let (primary, secondary) = futures::join!(
+            self.primary.instances(service),
+            self.secondary.instances(service)
+        );
+        match (primary, secondary) {
+            (Ok(mut primary), Ok(secondary)) => {
+                let known = primary.iter().map(instance_key).collect::<HashSet<_>>();
+                primary.extend(
+                    secondary
+                        .into_iter()
+                        .filter(|i| !known.contains(&instance_key(i))),
+                );
+                Ok(primary)
+            }
+            (primary, secondary) => either("instances", primary, secondary),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::build_test_instance;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeRegistry {
+        fail: bool,
+        missing: bool,
+        ports: Vec<u16>,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl FakeRegistry {
+        fn with_ports(ports: &[u16]) -> FakeRegistry {
+            FakeRegistry {
+                ports: ports.to_vec(),
+                ..FakeRegistry::default()
+            }
+        }
+
+        fn failing() -> FakeRegistry {
+            FakeRegistry {
+                fail: true,
+                ..FakeRegistry::default()
+            }
+        }
+
+        fn missing() -> FakeRegistry {
+            FakeRegistry {
+                missing: true,
+                ..FakeRegistry::default()
+            }
+        }
+
+        fn record(&self, call: String) -> Result<(), EurekaClientError> {
+            self.calls.lock().expect("test unwrap").push(call);
+            if self.missing {
+                Err(EurekaClientError::NotFound)
+            } else if self.fail {
+                Err(EurekaClientError::InternalServerError)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ServiceRegistry for FakeRegistry {
+        async fn register(&self, registration: &RegisterRequest) -> Result<(), EurekaClientError> {
+            self.record(format!("register {}", registration.instance.app))
+        }
+
+        async fn deregister(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError> {
+            self.record(format!("deregister {} {}", app, instance_id))
+        }
+
+        async fn heartbeat(&self, app: &str, instance_id: &str) -> Result<(), EurekaClientError> {
+            self.record(format!("heartbeat {} {}", app, instance_id))
+        }
+    }
+
+    #[async_trait]
+    impl ServiceDiscovery for FakeRegistry {
+        async fn instances(&self, _service: &str) -> Result<Vec<Instance>, EurekaClientError> {
+            self.record("instances".to_owned())?;
+            Ok(self
+                .ports
+                .iter()
+                .map(|port| {
+                    let mut instance = build_test_instance();
+                    instance.port = Some(*port);
+                    instance
+                })
+                .collect())
+        }
+    }
+
+    fn ports(instances: &[Instance]) -> Vec<Option<u16>> {
+        instances.iter().map(|i| i.port).collect()
+    }
+
+    #[tokio::test]
+    async fn test_dual_registration() {
+        let composite = CompositeRegistry::new(FakeRegistry::default(), FakeRegistry::failing());
+        composite
+            .heartbeat("BAR", "foo")
+            .await
+            .expect("the primary accepted it");
+        composite
+            .deregister("BAR", "foo")
+            .await
+            .expect("the primary accepted it");
+
+        for registry in [composite.primary(), composite.secondary()] {
+            assert_eq!(
+                vec!["heartbeat BAR foo", "deregister BAR foo"],
+                *registry.calls.lock().expect("test unwrap")
+            );
+        }
+
+        let composite = CompositeRegistry::new(FakeRegistry::failing(), FakeRegistry::failing());
+        assert!(composite.heartbeat("BAR", "foo").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_not_found_on_one_backend() {
+        let composite = CompositeRegistry::new(FakeRegistry::default(), FakeRegistry::missing());
+        assert!(matches!(
+            composite.heartbeat("BAR", "foo").await,
+            Err(EurekaClientError::NotFound)
+        ));
+        let composite = CompositeRegistry::new(FakeRegistry::missing(), FakeRegistry::default());
+        assert!(matches!(
+            composite.heartbeat("BAR", "foo").await,
+            Err(EurekaClientError::NotFound)
+        ));
+        // other operations still succeed on either backend
+        assert!(composite.deregister("BAR", "foo").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_merged_discovery() {
+        let composite = CompositeRegistry::new(
+            FakeRegistry::with_ports(&[8080, 8081]),
+            FakeRegistry::with_ports(&[8081, 8082]),
+        );
+        assert_eq!(
+            vec![Some(8080), Some(8081), Some(8082)],
+            ports(&composite.instances("bar").await.expect("test unwrap"))
+        );
+
+        let composite =
+            CompositeRegistry::new(FakeRegistry::failing(), FakeRegistry::with_ports(&[8082]));
+        assert_eq!(
+            vec![Some(8082)],
+            ports(&composite.instances("bar").await.expect("test unwrap"))
+        );
+    }
+}
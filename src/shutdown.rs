@@ -0,0 +1,158 @@
+use crate::errors::EurekaClientError;
+use crate::request::{RegisterRequest, Status};
+use crate::EurekaClient;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+/// How long callers get to stop routing to the instance before it is deregistered
+pub const DEFAULT_DRAIN_PERIOD: Duration = Duration::from_secs(15);
+/// The longest each call to Eureka may take during shutdown
+pub const DEFAULT_DEADLINE: Duration = Duration::from_secs(5);
+
+/// How the instance left Eureka
+#[derive(Debug)]
+pub enum ShutdownOutcome {
+    Deregistered,
+    /// Eureka rejected the deregistration, the lease will expire on its own
+    Failed(EurekaClientError),
+    /// Eureka did not answer before the deadline, the lease will expire on its own
+    TimedOut,
+}
+
+/// Takes the instance out of Eureka when the process is asked to stop: the instance
+/// is first marked `OUT_OF_SERVICE` through the status endpoint so callers stop routing
+/// to it, then deregistered once the drain period has passed.
+pub struct GracefulShutdown {
+    client: Arc<EurekaClient>,
+    registration: RegisterRequest,
+    instance_id: String,
+    status: Status,
+    drain_period: Duration,
+    deadline: Duration,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl GracefulShutdown {
+    /// Creates a new shutdown helper
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client used to update and deregister the instance
+    /// * `registration` - The registration sent to Eureka
+    pub fn new(client: Arc<EurekaClient>, registration: RegisterRequest) -> GracefulShutdown {
+        GracefulShutdown {
+            client,
+            instance_id: registration.instance.host_name.clone(),
+            registration,
+            status: Status::OutOfService,
+            drain_period: DEFAULT_DRAIN_PERIOD,
+            deadline: DEFAULT_DEADLINE,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// The id used to mark and deregister the instance, by default the host name as Eureka does
+    pub fn instance_id(mut self, instance_id: &str) -> GracefulShutdown {
+        self.instance_id = instance_id.to_owned();
+        self
+    }
+
+    /// The status reported while draining, `OUT_OF_SERVICE` or `DOWN`
+    pub fn status(mut self, status: Status) -> GracefulShutdown {
+        self.status = status;
+        self
+    }
+
+    pub fn drain_period(mut self, drain_period: Duration) -> GracefulShutdown {
+        self.drain_period = drain_period;
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Duration) -> GracefulShutdown {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Aborts the task when shutdown starts, e.g. a
+    /// [HealthReporter](../health/struct.HealthReporter.html) that would otherwise report `UP` again
+    pub fn abort_on_shutdown(mut self, task: JoinHandle<()>) -> GracefulShutdown {
+        self.tasks.push(task);
+        self
+    }
+
+    /// Waits for SIGTERM or SIGINT, then shuts down
+    pub async fn run(self) -> ShutdownOutcome {
+        if let Err(e) = wait_for_signal().await {
+            warn!(
+                "failed to listen for shutdown signals, shutting down now: {}",
+                e
+            );
+        }
+        self.shutdown().await
+    }
+
+    /// Marks the instance, waits for the drain period and deregisters it
+    pub async fn shutdown(self) -> ShutdownOutcome {
+        for task in &self.tasks {
+            task.abort();
+        }
+
+        // a re-registration would be ignored while the lease is UP, an override is not
+        let app = self.registration.instance.app.clone();
+        match time::timeout(
+            self.deadline,
+            self.client
+                .update_status(&app, &self.instance_id, &self.status),
+        )
+        .await
+        {
+            Ok(Ok(())) => info!("marked {} as {:?}", self.instance_id, self.status),
+            Ok(Err(e)) => warn!(
+                "failed to mark {} as {:?}: {}",
+                self.instance_id, self.status, e
+            ),
+            Err(_) => warn!(
+                "timed out marking {} as {:?}",
+                self.instance_id, self.status
+            ),
+        }
+
+        time::sleep(self.drain_period).await;
+
+        let outcome = match time::timeout(
+            self.deadline,
+            self.client.deregister(&app, &self.instance_id),
+        )
+        .await
+        {
+            Ok(Ok(())) => ShutdownOutcome::Deregistered,
+            Ok(Err(e)) => ShutdownOutcome::Failed(e),
+            Err(_) => ShutdownOutcome::TimedOut,
+        };
+        match &outcome {
+            ShutdownOutcome::Deregistered => info!("deregistered {}", self.instance_id),
+            other => warn!("failed to deregister {}: {:?}", self.instance_id, other),
+        }
+        outcome
+    }
+}
+
+/// Resolves once the process receives SIGTERM or SIGINT
+pub async fn wait_for_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await
+    }
+}
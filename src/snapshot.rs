@@ -0,0 +1,175 @@
+use crate::errors::EurekaClientError;
+use crate::index::RegistryIndex;
+use crate::persistence::SnapshotFile;
+use crate::response::Applications;
+use crate::EurekaClient;
+use arc_swap::ArcSwap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+
+/// Holds the latest registry snapshot for workloads that read far more often than the
+/// registry changes.
+///
+/// Each refresh builds a new immutable [RegistryIndex](../index/struct.RegistryIndex.html)
+/// and swaps it in atomically, so readers never take a lock or wait on a refresh, and keep
+/// using the snapshot they loaded until they drop it.
+///
+/// With a [SnapshotFile](../persistence/struct.SnapshotFile.html), every successful refresh
+/// is saved to disk and [warm_start](#method.warm_start) serves the saved snapshot, marked as
+/// stale, until Eureka can be reached again.
+pub struct SnapshotRegistry {
+    current: ArcSwap<RegistryIndex>,
+    stale: AtomicBool,
+    file: Option<SnapshotFile>,
+}
+
+impl SnapshotRegistry {
+    /// Creates a registry holding an empty snapshot
+    pub fn new() -> SnapshotRegistry {
+        SnapshotRegistry {
+            current: ArcSwap::from_pointee(RegistryIndex::default()),
+            stale: AtomicBool::new(false),
+            file: None,
+        }
+    }
+
+    /// Creates a registry holding the snapshot saved in the file, if any, marked as stale
+    /// until a refresh succeeds. Refreshes are saved to the same file.
+    pub async fn warm_start(file: SnapshotFile) -> SnapshotRegistry {
+        let registry = SnapshotRegistry::new();
+        match file.load().await {
+            Ok(Some(snapshot)) => {
+                info!(
+                    "loaded registry snapshot fetched from {} at {}",
+                    snapshot.header.source, snapshot.header.fetched_at
+                );
+                registry.publish(snapshot.response.applications);
+                registry.stale.store(true, Ordering::Release);
+            }
+            Ok(None) => debug!("no registry snapshot at {:?}", file.path()),
+            Err(e) => warn!(
+                "failed to load the registry snapshot at {:?}: {}",
+                file.path(),
+                e
+            ),
+        }
+        registry.persist_to(file)
+    }
+
+    /// Saves every successful refresh to the file
+    pub fn persist_to(mut self, file: SnapshotFile) -> SnapshotRegistry {
+        self.file = Some(file);
+        self
+    }
+
+    /// The current snapshot
+    pub fn load(&self) -> Arc<RegistryIndex> {
+        self.current.load_full()
+    }
+
+    /// Whether the current snapshot was loaded from disk and no refresh succeeded since
+    pub fn is_stale(&self) -> bool {
+        self.stale.load(Ordering::Acquire)
+    }
+
+    /// Indexes the applications and publishes them as the current, fresh snapshot
+    pub fn publish(&self, applications: Applications) -> Arc<RegistryIndex> {
+        let snapshot = Arc::new(RegistryIndex::new(applications));
+        self.current.store(snapshot.clone());
+        self.stale.store(false, Ordering::Release);
+        snapshot
+    }
+
+    /// Fetches every application and publishes them, keeping the current snapshot on failure.
+    /// A failure to save the snapshot to disk is logged and does not fail the refresh.
+    pub async fn refresh(&self, client: &EurekaClient) -> Result<(), EurekaClientError> {
+        let (res, source) = client.get_applications_with_source().await?;
+        if let Some(file) = &self.file {
+            if let Err(e) = file.save(&res, source.as_str()).await {
+                warn!(
+                    "failed to save the registry snapshot to {:?}: {}",
+                    file.path(),
+                    e
+                );
+            }
+        }
+        self.publish(res.applications);
+        Ok(())
+    }
+
+    /// Refreshes the snapshot every `interval` on the tokio runtime until the returned
+    /// handle is aborted. Failed refreshes are logged and retried at the next interval.
+    pub fn spawn_refresh(
+        self: &Arc<Self>,
+        client: Arc<EurekaClient>,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if let Err(e) = registry.refresh(&client).await {
+                    warn!("failed to refresh the registry snapshot: {}", e);
+                }
+            }
+        })
+    }
+}
+
+impl Default for SnapshotRegistry {
+    fn default() -> Self {
+        SnapshotRegistry::new()
+    }
+}
+
+impl From<Applications> for SnapshotRegistry {
+    fn from(applications: Applications) -> Self {
+        SnapshotRegistry {
+            current: ArcSwap::from_pointee(RegistryIndex::new(applications)),
+            stale: AtomicBool::new(false),
+            file: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{build_test_instance, Application};
+
+    fn build_test_applications(hosts: &[&str]) -> Applications {
+        Applications {
+            versions_delta: 1,
+            apps_hashcode: String::new(),
+            applications: vec![Application {
+                name: "BAR".into(),
+                instance: hosts
+                    .iter()
+                    .map(|h| {
+                        let mut instance = build_test_instance();
+                        instance.host_name = h.to_string();
+                        instance
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_readers_keep_their_snapshot() {
+        let registry = SnapshotRegistry::new();
+        assert!(registry.load().is_empty());
+
+        registry.publish(build_test_applications(&["a"]));
+        let before = registry.load();
+        registry.publish(build_test_applications(&["a", "b"]));
+
+        assert_eq!(1, before.app("bar").count());
+        assert_eq!(2, registry.load().app("bar").count());
+    }
+}
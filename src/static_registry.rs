@@ -0,0 +1,345 @@
+use crate::errors::EurekaClientError;
+use crate::load_balancer::url_host;
+use crate::response::{intern, ActionType, Application, DataCenterInfo, DcName, Instance, Status};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Environment variables starting with this prefix define the instances of an app,
+/// e.g. `EUREKA_STATIC_INVENTORY=host1:8080,host2:8080`
+pub const STATIC_INVENTORY_PREFIX: &str = "EUREKA_STATIC_";
+
+/// An instance in a static registry file, either `"host:port"` or a table
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum InstanceDefinition {
+    Address(String),
+    Detailed(StaticInstance),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StaticInstance {
+    host: String,
+    port: Option<u16>,
+    secure_port: Option<u16>,
+    zone: Option<String>,
+    #[serde(default)]
+    metadata: Map<String, Value>,
+}
+
+/// A fixed set of applications, for discovery without Eureka during local development
+/// or when every Eureka server is down.
+///
+/// Files map app names to their instances, each either `"host:port"` or a table with
+/// `host`, `port`, `securePort`, `zone` and `metadata`:
+///
+/// ```toml
+/// inventory-service = ["10.0.0.1:8080", "10.0.0.2:8080"]
+///
+/// [[order-service]]
+/// host = "10.0.0.3"
+/// port = 8080
+/// zone = "us-east-1a"
+/// ```
+///
+/// Every instance is reported as `UP`. App names are matched ignoring case, and `-` and `_`
+/// are interchangeable so names taken from environment variables still match.
+#[derive(Debug, Clone, Default)]
+pub struct StaticRegistry {
+    applications: HashMap<String, Arc<Application>>,
+}
+
+impl StaticRegistry {
+    /// Creates an empty registry
+    pub fn new() -> StaticRegistry {
+        StaticRegistry::default()
+    }
+
+    /// Reads the applications from a file, parsed as TOML when its extension is `.toml`
+    /// and as JSON otherwise
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<StaticRegistry, EurekaClientError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("toml"))
+        {
+            StaticRegistry::from_toml(&contents)
+        } else {
+            StaticRegistry::from_json(&contents)
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<StaticRegistry, EurekaClientError> {
+        let apps: HashMap<String, Vec<InstanceDefinition>> = serde_json::from_str(json)?;
+        StaticRegistry::from_definitions(apps)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<StaticRegistry, EurekaClientError> {
+        let apps: HashMap<String, Vec<InstanceDefinition>> = toml::from_str(toml)
+            .map_err(|e| EurekaClientError::GenericError(format!("Invalid TOML: {}", e)))?;
+        StaticRegistry::from_definitions(apps)
+    }
+
+    /// Reads the applications from the `EUREKA_STATIC_<APP>` environment variables
+    pub fn from_env() -> Result<StaticRegistry, EurekaClientError> {
+        StaticRegistry::from_vars(env::vars())
+    }
+
+    fn from_vars<I>(vars: I) -> Result<StaticRegistry, EurekaClientError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut registry = StaticRegistry::new();
+        for (key, value) in vars {
+            if let Some(app) = key.strip_prefix(STATIC_INVENTORY_PREFIX) {
+                let addresses = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .collect::<Vec<_>>();
+                registry = registry.with_app(app, &addresses)?;
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Adds an app, replacing any previous definition
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The app name
+    /// * `addresses` - The `host:port` of each instance
+    pub fn with_app(
+        mut self,
+        name: &str,
+        addresses: &[&str],
+    ) -> Result<StaticRegistry, EurekaClientError> {
+        let definitions = addresses
+            .iter()
+            .map(|a| InstanceDefinition::Address(a.to_string()))
+            .collect();
+        self.insert(name, definitions)?;
+        Ok(self)
+    }
+
+    /// Looks up an application by name
+    pub fn get(&self, name: &str) -> Option<Arc<Application>> {
+        self.applications.get(&normalize(name)).cloned()
+    }
+
+    pub fn applications(&self) -> impl Iterator<Item = &Application> {
+        self.applications.values().map(|a| a.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.applications.is_empty()
+    }
+
+    fn from_definitions(
+        apps: HashMap<String, Vec<InstanceDefinition>>,
+    ) -> Result<StaticRegistry, EurekaClientError> {
+        let mut registry = StaticRegistry::new();
+        for (name, definitions) in apps {
+            registry.insert(&name, definitions)?;
+        }
+        Ok(registry)
+    }
+
+    fn insert(
+        &mut self,
+        name: &str,
+        definitions: Vec<InstanceDefinition>,
+    ) -> Result<(), EurekaClientError> {
+        let app = intern(&name.to_ascii_uppercase());
+        let vip = intern(&name.to_ascii_lowercase());
+        let instance = definitions
+            .into_iter()
+            .map(|d| build_instance(&app, &vip, d))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.applications.insert(
+            normalize(name),
+            Arc::new(Application {
+                name: app,
+                instance,
+            }),
+        );
+        Ok(())
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.to_ascii_uppercase().replace('-', "_")
+}
+
+fn parse_address(address: &str) -> Result<StaticInstance, EurekaClientError> {
+    let invalid = || {
+        EurekaClientError::GenericError(format!(
+            "Invalid static instance {:?}, expected host:port",
+            address
+        ))
+    };
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok(StaticInstance {
+        host: host.to_owned(),
+        port: Some(port.parse().map_err(|_| invalid())?),
+        secure_port: None,
+        zone: None,
+        metadata: Map::new(),
+    })
+}
+
+fn build_instance(
+    app: &Arc<str>,
+    vip: &Arc<str>,
+    definition: InstanceDefinition,
+) -> Result<Instance, EurekaClientError> {
+    let definition = match definition {
+        InstanceDefinition::Address(address) => parse_address(&address)?,
+        InstanceDefinition::Detailed(instance) => instance,
+    };
+    let home_page_url = match (definition.port, definition.secure_port) {
+        (Some(port), _) => format!("http://{}:{}/", url_host(&definition.host), port),
+        (None, Some(port)) => format!("https://{}:{}/", url_host(&definition.host), port),
+        (None, None) => {
+            return Err(EurekaClientError::GenericError(format!(
+                "Static instance {} of {} has no port",
+                definition.host, app
+            )))
+        }
+    };
+    let custom_metadata = definition.zone.map(|zone| {
+        let mut metadata = Map::new();
+        metadata.insert("zone".to_owned(), Value::from(zone));
+        metadata
+    });
+
+    Ok(Instance {
+        instance_id: Some(format!(
+            "{}:{}",
+            url_host(&definition.host),
+            definition
+                .port
+                .or(definition.secure_port)
+                .unwrap_or_default()
+        )),
+        host_name: definition.host.clone(),
+        app: app.clone(),
+        ip_addr: definition.host,
+        vip_address: vip.clone(),
+        secure_vip_address: vip.clone(),
+        status: Status::Up,
+        port: definition.port,
+        secure_port: definition.secure_port,
+        status_page_url: format!("{}status", home_page_url),
+        health_check_url: format!("{}health", home_page_url),
+        homepage_url: home_page_url,
+        data_center_info: DataCenterInfo {
+            name: DcName::MyOwn,
+            metadata: None,
+            custom_metadata,
+        },
+        lease_info: None,
+        metadata: definition.metadata,
+        overriddenstatus: None,
+        country_id: 1,
+        last_updated_timestamp: 0,
+        last_dirty_timestamp: 0,
+        action_type: ActionType::Added,
+        is_coordinating_discovery_server: false,
+        region: None,
+        cluster: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(registry: &StaticRegistry, name: &str) -> Vec<Option<u16>> {
+        registry
+            .get(name)
+            .map(|a| a.instance.iter().map(|i| i.port).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let registry = StaticRegistry::from_toml(
+            r#"
+            inventory-service = ["10.0.0.1:8080", "10.0.0.2:8081"]
+
+            [[order-service]]
+            host = "10.0.0.3"
+            securePort = 8443
+            zone = "us-east-1a"
+            metadata = { weight = "5" }
+            "#,
+        )
+        .expect("test unwrap");
+
+        assert_eq!(
+            vec![Some(8080), Some(8081)],
+            ports(&registry, "INVENTORY-SERVICE")
+        );
+        let order = registry.get("order_service").expect("test unwrap");
+        assert_eq!("ORDER-SERVICE", &*order.name);
+        let instance = &order.instance[0];
+        assert_eq!(Some(8443), instance.secure_port);
+        assert_eq!("https://10.0.0.3:8443/", instance.homepage_url);
+        assert_eq!("order-service", &*instance.vip_address);
+        assert_eq!(Some("us-east-1a"), instance.data_center_info.zone());
+        assert_eq!(Some(&Value::from("5")), instance.metadata.get("weight"));
+        assert_eq!(Status::Up, instance.effective_status());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let registry = StaticRegistry::from_json(
+            r#"{"inventory": ["[::1]:8080", {"host": "b", "port": 9090}]}"#,
+        )
+        .expect("test unwrap");
+        let inventory = registry.get("inventory").expect("test unwrap");
+        assert_eq!("::1", inventory.instance[0].host_name);
+        assert_eq!("[::1]:8080", inventory.instance[0].id());
+        let url = reqwest::Url::parse(&inventory.instance[0].homepage_url).expect("test unwrap");
+        assert_eq!(Some("[::1]"), url.host_str());
+        assert_eq!(Some(8080), url.port());
+        assert_eq!("b:9090", inventory.instance[1].id());
+
+        assert!(StaticRegistry::from_json(r#"{"inventory": ["no-port"]}"#).is_err());
+        assert!(StaticRegistry::from_json(r#"{"inventory": [{"host": "b"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let registry = StaticRegistry::from_vars(vec![
+            (
+                "EUREKA_STATIC_INVENTORY_SERVICE".to_owned(),
+                "host1:8080, host2:8080".to_owned(),
+            ),
+            ("PATH".to_owned(), "/usr/bin".to_owned()),
+        ])
+        .expect("test unwrap");
+
+        assert_eq!(1, registry.applications().count());
+        assert_eq!(
+            vec![Some(8080), Some(8080)],
+            ports(&registry, "inventory-service")
+        );
+        assert!(StaticRegistry::from_vars(vec![(
+            "EUREKA_STATIC_INVENTORY".to_owned(),
+            "host1:http".to_owned()
+        )])
+        .is_err());
+    }
+}
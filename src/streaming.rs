@@ -0,0 +1,557 @@
+use crate::errors::EurekaClientError;
+use crate::response::{intern, Application, Applications, Instance, Status};
+use bytes::Bytes;
+use futures::stream::{Stream, StreamExt};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::sync::mpsc;
+
+/// The largest response body accepted by default, after decompression
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 64 * 1024 * 1024;
+
+// body chunks waiting for the parser
+const CHUNK_BUFFER: usize = 8;
+// parsed applications waiting for the consumer
+const PARSED_BUFFER: usize = 16;
+
+type InstanceFilter = Box<dyn Fn(&Instance) -> bool + Send>;
+
+/// Options for [EurekaClient::stream_applications](../eureka_client/struct.EurekaClient.html#method.stream_applications)
+pub struct StreamOptions {
+    max_response_size: usize,
+    apps: Option<HashSet<String>>,
+    instance_filters: Vec<InstanceFilter>,
+}
+
+impl StreamOptions {
+    /// Creates new options keeping every application, up to a 64 MiB response
+    pub fn new() -> StreamOptions {
+        StreamOptions {
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            apps: None,
+            instance_filters: Vec::new(),
+        }
+    }
+
+    /// The largest response body accepted, in bytes after decompression
+    pub fn max_response_size(mut self, max_response_size: usize) -> StreamOptions {
+        self.max_response_size = max_response_size;
+        self
+    }
+
+    /// Only keeps these apps, ignoring case. The instances of other apps are skipped
+    /// without being built.
+    pub fn apps(mut self, names: &[&str]) -> StreamOptions {
+        self.apps = Some(names.iter().map(|n| n.to_ascii_uppercase()).collect());
+        self
+    }
+
+    /// Only keeps the instances whose effective status is `UP`
+    pub fn up_only(self) -> StreamOptions {
+        self.filter_instances(|i| i.effective_status() == Status::Up)
+    }
+
+    /// Only keeps the instances matching the predicate, along with any previous filters
+    pub fn filter_instances<F>(mut self, filter: F) -> StreamOptions
+    where
+        F: Fn(&Instance) -> bool + Send + 'static,
+    {
+        self.instance_filters.push(Box::new(filter));
+        self
+    }
+
+    fn accepts_app(&self, name: &str) -> bool {
+        self.apps
+            .as_ref()
+            .is_none_or(|apps| apps.contains(&name.to_ascii_uppercase()))
+    }
+
+    fn accepts_instance(&self, instance: &Instance) -> bool {
+        self.instance_filters.iter().all(|f| f(instance))
+    }
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        StreamOptions::new()
+    }
+}
+
+enum Parsed {
+    VersionsDelta(i16),
+    AppsHashcode(String),
+    Application(Application),
+}
+
+type ParsedSender = mpsc::Sender<Result<Parsed, EurekaClientError>>;
+
+/// The applications of a registry response, yielded as they are parsed from the body.
+///
+/// The body is read chunk by chunk and each application is built, filtered and handed
+/// over before the next one is parsed, so memory stays bounded by the size of the
+/// applications kept rather than the size of the response.
+pub struct ApplicationStream {
+    parsed: mpsc::Receiver<Result<Parsed, EurekaClientError>>,
+    versions_delta: Option<i16>,
+    apps_hashcode: Option<String>,
+    done: bool,
+}
+
+impl ApplicationStream {
+    pub(crate) fn new(
+        res: reqwest::Response,
+        options: StreamOptions,
+    ) -> Result<ApplicationStream, EurekaClientError> {
+        let limit = options.max_response_size;
+        if res.content_length().is_some_and(|len| len > limit as u64) {
+            return Err(EurekaClientError::ResponseTooLarge(limit));
+        }
+
+        let (chunk_tx, chunk_rx) = mpsc::channel(CHUNK_BUFFER);
+        let (parsed_tx, parsed_rx) = mpsc::channel(PARSED_BUFFER);
+
+        // closing the chunk channel early makes the parser fail, after the reason was sent
+        let body_tx = parsed_tx.clone();
+        tokio::spawn(async move {
+            let mut body = res.bytes_stream();
+            let mut received = 0;
+            while let Some(chunk) = body.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        let _ = body_tx.send(Err(EurekaClientError::from(e))).await;
+                        return;
+                    }
+                };
+                received += chunk.len();
+                if received > limit {
+                    let _ = body_tx
+                        .send(Err(EurekaClientError::ResponseTooLarge(limit)))
+                        .await;
+                    return;
+                }
+                if chunk_tx.send(chunk).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        tokio::task::spawn_blocking(move || {
+            let reader = ChunkReader {
+                chunks: chunk_rx,
+                current: Bytes::new(),
+            };
+            if let Err(e) = parse(reader, &options, &parsed_tx) {
+                if !parsed_tx.is_closed() {
+                    let _ = parsed_tx.blocking_send(Err(EurekaClientError::from(e)));
+                }
+            }
+        });
+
+        Ok(ApplicationStream {
+            parsed: parsed_rx,
+            versions_delta: None,
+            apps_hashcode: None,
+            done: false,
+        })
+    }
+
+    /// The `versions__delta` of the response, once it was parsed
+    pub fn versions_delta(&self) -> Option<i16> {
+        self.versions_delta
+    }
+
+    /// The `apps__hashcode` of the response, once it was parsed
+    pub fn apps_hashcode(&self) -> Option<&str> {
+        self.apps_hashcode.as_deref()
+    }
+
+    /// Collects the remaining applications
+    pub async fn into_applications(mut self) -> Result<Applications, EurekaClientError> {
+        let mut applications = Vec::new();
+        while let Some(application) = self.next().await {
+            applications.push(application?);
+        }
+        Ok(Applications {
+            versions_delta: self.versions_delta.unwrap_or_default(),
+            apps_hashcode: self.apps_hashcode.unwrap_or_default(),
+            applications,
+        })
+    }
+}
+
+impl Stream for ApplicationStream {
+    type Item = Result<Application, EurekaClientError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        while !self.done {
+            match ready!(self.parsed.poll_recv(cx)) {
+                Some(Ok(Parsed::Application(application))) => {
+                    return Poll::Ready(Some(Ok(application)))
+                }
+                Some(Ok(Parsed::VersionsDelta(versions_delta))) => {
+                    self.versions_delta = Some(versions_delta)
+                }
+                Some(Ok(Parsed::AppsHashcode(apps_hashcode))) => {
+                    self.apps_hashcode = Some(apps_hashcode)
+                }
+                Some(Err(e)) => {
+                    // stops the parser
+                    self.parsed.close();
+                    self.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => self.done = true,
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+/// Feeds the body chunks to the blocking parser
+struct ChunkReader {
+    chunks: mpsc::Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
+    }
+}
+
+fn parse<R: Read>(
+    reader: R,
+    options: &StreamOptions,
+    out: &ParsedSender,
+) -> serde_json::Result<()> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    Root(Parser { options, out }).deserialize(&mut deserializer)?;
+    deserializer.end()
+}
+
+#[derive(Clone, Copy)]
+struct Parser<'a> {
+    options: &'a StreamOptions,
+    out: &'a ParsedSender,
+}
+
+impl Parser<'_> {
+    fn send<E: de::Error>(&self, parsed: Parsed) -> Result<(), E> {
+        self.out
+            .blocking_send(Ok(parsed))
+            .map_err(|_| E::custom("the application stream was dropped"))
+    }
+}
+
+// servers return versions__delta and apps__hashcode as strings or numbers
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    Number(i64),
+    Text(String),
+}
+
+/// `{"applications": {...}}`
+struct Root<'a>(Parser<'a>);
+
+impl<'de> DeserializeSeed<'de> for Root<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Root<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct ApplicationsResponse")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "applications" {
+                map.next_value_seed(ApplicationsSeed(self.0))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `{"versions__delta": .., "apps__hashcode": .., "application": [...]}`
+struct ApplicationsSeed<'a>(Parser<'a>);
+
+impl<'de> DeserializeSeed<'de> for ApplicationsSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ApplicationsSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct Applications")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "versions__delta" => {
+                    let versions_delta = match map.next_value()? {
+                        Scalar::Number(n) => i16::try_from(n).ok(),
+                        Scalar::Text(s) => s.parse().ok(),
+                    }
+                    .ok_or_else(|| de::Error::custom("Invalid versions__delta type"))?;
+                    self.0.send(Parsed::VersionsDelta(versions_delta))?;
+                }
+                "apps__hashcode" => {
+                    let apps_hashcode = match map.next_value()? {
+                        Scalar::Number(n) => n.to_string(),
+                        Scalar::Text(s) => s,
+                    };
+                    self.0.send(Parsed::AppsHashcode(apps_hashcode))?;
+                }
+                "application" => map.next_value_seed(ApplicationList(self.0))?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single application or an array of them
+struct ApplicationList<'a>(Parser<'a>);
+
+impl<'de> DeserializeSeed<'de> for ApplicationList<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ApplicationList<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("application or array of applications")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<(), A::Error> {
+        ApplicationSeed(self.0).visit_map(map)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq.next_element_seed(ApplicationSeed(self.0))?.is_some() {}
+        Ok(())
+    }
+}
+
+/// One application, sent to the stream unless it is filtered out
+struct ApplicationSeed<'a>(Parser<'a>);
+
+impl<'de> DeserializeSeed<'de> for ApplicationSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ApplicationSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct Application")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut name: Option<Arc<str>> = None;
+        let mut instances = Vec::new();
+        let mut skip = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "name" => {
+                    let value = intern(&map.next_value::<String>()?);
+                    skip = !self.0.options.accepts_app(&value);
+                    if skip {
+                        instances = Vec::new();
+                    }
+                    name = Some(value);
+                }
+                "instance" if skip => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+                "instance" => map.next_value_seed(InstanceList {
+                    options: self.0.options,
+                    instances: &mut instances,
+                })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let name = name.ok_or_else(|| de::Error::missing_field("name"))?;
+        if skip {
+            return Ok(());
+        }
+        self.0.send(Parsed::Application(Application {
+            name,
+            instance: instances,
+        }))
+    }
+}
+
+/// A single instance or an array of them, keeping those accepted by the filters
+struct InstanceList<'a> {
+    options: &'a StreamOptions,
+    instances: &'a mut Vec<Instance>,
+}
+
+impl<'de> DeserializeSeed<'de> for InstanceList<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for InstanceList<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("instance or array of instances")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<(), A::Error> {
+        let instance = Instance::deserialize(de::value::MapAccessDeserializer::new(map))?;
+        if self.options.accepts_instance(&instance) {
+            self.instances.push(instance);
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(instance) = seq.next_element::<Instance>()? {
+            if self.options.accepts_instance(&instance) {
+                self.instances.push(instance);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::build_test_instance;
+
+    fn build_test_response_json() -> String {
+        let mut down = build_test_instance();
+        down.host_name = "Down".to_owned();
+        down.status = Status::Down;
+        let app = |name: &str, instances: Vec<Instance>| Application {
+            name: name.into(),
+            instance: instances,
+        };
+        let applications = vec![
+            app("BAR", vec![build_test_instance(), down]),
+            app("BAZ", vec![build_test_instance()]),
+        ];
+        format!(
+            r#"{{"applications":{{"versions__delta":"3","apps__hashcode":"UP_3_","application":{}}}}}"#,
+            serde_json::to_string(&applications).expect("test unwrap")
+        )
+    }
+
+    fn parse_all(json: &str, options: StreamOptions) -> (serde_json::Result<()>, Vec<Parsed>) {
+        let (tx, mut rx) = mpsc::channel(PARSED_BUFFER);
+        let result = parse(json.as_bytes(), &options, &tx);
+        let mut parsed = Vec::new();
+        while let Ok(p) = rx.try_recv() {
+            parsed.push(p.expect("test unwrap"));
+        }
+        (result, parsed)
+    }
+
+    fn summarize(parsed: &[Parsed]) -> Vec<String> {
+        parsed
+            .iter()
+            .map(|p| match p {
+                Parsed::VersionsDelta(v) => format!("delta {}", v),
+                Parsed::AppsHashcode(h) => format!("hashcode {}", h),
+                Parsed::Application(a) => format!("{} {}", a.name, a.instance.len()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let (result, parsed) = parse_all(&build_test_response_json(), StreamOptions::new());
+        result.expect("test unwrap");
+        assert_eq!(
+            vec!["delta 3", "hashcode UP_3_", "BAR 2", "BAZ 1"],
+            summarize(&parsed)
+        );
+    }
+
+    #[test]
+    fn test_parse_with_filters() {
+        let options = StreamOptions::new().apps(&["bar"]).up_only();
+        let (result, parsed) = parse_all(&build_test_response_json(), options);
+        result.expect("test unwrap");
+        assert_eq!(
+            vec!["delta 3", "hashcode UP_3_", "BAR 1"],
+            summarize(&parsed)
+        );
+    }
+
+    #[test]
+    fn test_parse_single_application() {
+        let json = format!(
+            r#"{{"applications":{{"versions__delta":1,"apps__hashcode":"","application":{{"instance":[{}],"name":"BAR"}}}}}}"#,
+            serde_json::to_string(&build_test_instance()).expect("test unwrap")
+        );
+        let (result, parsed) = parse_all(&json, StreamOptions::new());
+        result.expect("test unwrap");
+        assert_eq!(vec!["delta 1", "hashcode ", "BAR 1"], summarize(&parsed));
+
+        // the name comes last, so the instances are dropped once it is known
+        let (result, parsed) = parse_all(&json, StreamOptions::new().apps(&["BAZ"]));
+        result.expect("test unwrap");
+        assert_eq!(vec!["delta 1", "hashcode "], summarize(&parsed));
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let json = build_test_response_json();
+        let (result, parsed) = parse_all(&json[..json.len() / 2], StreamOptions::new());
+        assert!(result.is_err());
+        assert_eq!(vec!["delta 3", "hashcode UP_3_"], summarize(&parsed));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::errors::EurekaClientError;
+use crate::response::{Application, Applications, Instance};
+use crate::EurekaClient;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use std::collections::{BTreeMap, VecDeque};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{self, MissedTickBehavior};
+
+/// How often the registry is polled by default, matching Eureka's registry fetch interval
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How a watch fetches registry changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FetchMode {
+    /// Fetch every application on each poll
+    Full,
+    /// Fetch every application once, then only the delta on each poll. Every application
+    /// is fetched again when the registry no longer matches the delta's hashcode.
+    Delta,
+}
+
+/// Options for [EurekaClient::watch](../eureka_client/struct.EurekaClient.html#method.watch)
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchConfig {
+    /// The time between polls
+    pub interval: Duration,
+    /// When a poll sees a change, wait this long and poll again before reporting it,
+    /// so an instance that flaps within the window produces no events
+    pub coalesce: Duration,
+    pub mode: FetchMode,
+}
+
+impl WatchConfig {
+    /// Creates a new config with full fetches every 30 seconds and no coalescing
+    pub fn new() -> WatchConfig {
+        WatchConfig {
+            interval: DEFAULT_POLL_INTERVAL,
+            coalesce: Duration::ZERO,
+            mode: FetchMode::Full,
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> WatchConfig {
+        self.interval = interval;
+        self
+    }
+
+    pub fn coalesce(mut self, coalesce: Duration) -> WatchConfig {
+        self.coalesce = coalesce;
+        self
+    }
+
+    pub fn mode(mut self, mode: FetchMode) -> WatchConfig {
+        self.mode = mode;
+        self
+    }
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig::new()
+    }
+}
+
+/// A change to the instances of a watched app or VIP
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegistryEvent {
+    /// A new instance was registered. Every instance is reported as added on the first poll.
+    Added(Instance),
+    /// An instance was removed from the registry
+    Removed(Instance),
+    /// The [effective status](../response/struct.Instance.html#method.effective_status) of an
+    /// instance changed, including through a status override
+    StatusChanged {
+        before: Instance,
+        after: Instance,
+    },
+    MetadataChanged {
+        before: Instance,
+        after: Instance,
+    },
+}
+
+/// The stream returned by [EurekaClient::watch](../eureka_client/struct.EurekaClient.html#method.watch)
+pub struct RegistryWatch {
+    events: BoxStream<'static, RegistryEvent>,
+}
+
+impl Stream for RegistryWatch {
+    type Item = RegistryEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+// instance id -> instance, ordered so events come out in a stable order
+type Snapshot = BTreeMap<String, Instance>;
+
+struct WatchState {
+    client: Arc<EurekaClient>,
+    name: String,
+    config: WatchConfig,
+    snapshot: Option<Snapshot>,
+    // the whole registry the deltas are applied to, in delta mode
+    registry: Option<Applications>,
+    pending: VecDeque<RegistryEvent>,
+    interval: time::Interval,
+}
+
+impl WatchState {
+    async fn poll(&mut self) -> Result<(), EurekaClientError> {
+        let mut next = self.fetch().await?;
+        let changed = self.snapshot.as_ref().is_some_and(|s| *s != next);
+        if changed && !self.config.coalesce.is_zero() {
+            time::sleep(self.config.coalesce).await;
+            next = self.fetch().await?;
+        }
+        let previous = self.snapshot.take().unwrap_or_default();
+        self.pending.extend(diff(&previous, &next));
+        self.snapshot = Some(next);
+        Ok(())
+    }
+
+    async fn fetch(&mut self) -> Result<Snapshot, EurekaClientError> {
+        let registry = match (self.config.mode, &self.registry) {
+            (FetchMode::Delta, Some(registry)) => {
+                let delta = self.client.get_delta().await?.applications;
+                let next =
+                    registry.apply_delta(delta.applications.iter().flat_map(|a| a.instance.iter()));
+                if next.apps_hashcode == delta.apps_hashcode {
+                    next
+                } else {
+                    // Eureka's clients also fetch everything when the hashcodes disagree
+                    warn!(
+                        "registry hashcode {} does not match delta hashcode {}, fetching all applications",
+                        next.apps_hashcode, delta.apps_hashcode
+                    );
+                    self.client.get_applications().await?.applications
+                }
+            }
+            _ => self.client.get_applications().await?.applications,
+        };
+        let snapshot = self
+            .matching(&registry.applications)
+            .map(|i| (i.id().to_owned(), i.clone()))
+            .collect();
+        if self.config.mode == FetchMode::Delta {
+            self.registry = Some(registry);
+        }
+        Ok(snapshot)
+    }
+
+    fn matching<'a>(
+        &'a self,
+        applications: &'a [Application],
+    ) -> impl Iterator<Item = &'a Instance> + 'a {
+        applications
+            .iter()
+            .flat_map(|a| a.instance.iter())
+            .filter(move |i| {
+                i.app.eq_ignore_ascii_case(&self.name)
+                    || i.vip_address.eq_ignore_ascii_case(&self.name)
+                    || i.secure_vip_address.eq_ignore_ascii_case(&self.name)
+            })
+    }
+}
+
+impl EurekaClient {
+    /// Polls the registry and streams the changes to the instances of an app or VIP.
+    /// Failed polls are logged and retried at the next interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `app_or_vip` - The app name, VIP address or secure VIP address to watch
+    /// * `config` - The polling interval, coalescing window and fetch mode
+    pub fn watch(self: &Arc<Self>, app_or_vip: &str, config: WatchConfig) -> RegistryWatch {
+        let mut interval = time::interval(config.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let state = WatchState {
+            client: self.clone(),
+            name: app_or_vip.to_owned(),
+            config,
+            snapshot: None,
+            registry: None,
+            pending: VecDeque::new(),
+            interval,
+        };
+
+        let events = stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(event) = state.pending.pop_front() {
+                    return Some((event, state));
+                }
+                state.interval.tick().await;
+                if let Err(e) = state.poll().await {
+                    warn!("failed to poll instances of {}: {}", state.name, e);
+                }
+            }
+        });
+
+        RegistryWatch {
+            events: events.boxed(),
+        }
+    }
+}
+
+/// Instances that moved to another address are reported as removed and added again
+fn diff(before: &Snapshot, after: &Snapshot) -> Vec<RegistryEvent> {
+    let mut events = Vec::new();
+    for (id, old) in before {
+        match after.get(id) {
+            None => events.push(RegistryEvent::Removed(old.clone())),
+            Some(new) if address(old) != address(new) => {
+                events.push(RegistryEvent::Removed(old.clone()));
+                events.push(RegistryEvent::Added(new.clone()));
+            }
+            Some(new) => {
+                if old.effective_status() != new.effective_status() {
+                    events.push(RegistryEvent::StatusChanged {
+                        before: old.clone(),
+                        after: new.clone(),
+                    });
+                }
+                if old.metadata != new.metadata {
+                    events.push(RegistryEvent::MetadataChanged {
+                        before: old.clone(),
+                        after: new.clone(),
+                    });
+                }
+            }
+        }
+    }
+    for (id, new) in after {
+        if !before.contains_key(id) {
+            events.push(RegistryEvent::Added(new.clone()));
+        }
+    }
+    events
+}
+
+fn address(instance: &Instance) -> (&str, &str, Option<u16>, Option<u16>) {
+    (
+        &instance.host_name,
+        &instance.ip_addr,
+        instance.port,
+        instance.secure_port,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{build_test_instance, Status};
+    use serde_json::Value;
+
+    fn snapshot(instances: &[&Instance]) -> Snapshot {
+        instances
+            .iter()
+            .map(|i| (i.id().to_owned(), (*i).clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        let a = build_test_instance();
+        let mut b = build_test_instance();
+        b.host_name = "b".to_owned();
+
+        let events = diff(&snapshot(&[&a]), &snapshot(&[&b]));
+        assert_eq!(
+            vec![RegistryEvent::Removed(a), RegistryEvent::Added(b)],
+            events
+        );
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let before = build_test_instance();
+        let mut after = before.clone();
+        after.status = Status::Down;
+        after
+            .metadata
+            .insert("version".to_owned(), Value::from("2"));
+
+        let events = diff(&snapshot(&[&before]), &snapshot(&[&after]));
+        assert_eq!(
+            vec![
+                RegistryEvent::StatusChanged {
+                    before: before.clone(),
+                    after: after.clone(),
+                },
+                RegistryEvent::MetadataChanged { before, after },
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn test_diff_status_override() {
+        let before = build_test_instance();
+        let mut after = before.clone();
+        after.overriddenstatus = Some(Status::OutOfService);
+        let events = diff(&snapshot(&[&before]), &snapshot(&[&after]));
+        assert_eq!(
+            vec![RegistryEvent::StatusChanged {
+                before: before.clone(),
+                after,
+            }],
+            events
+        );
+
+        // an instance reporting itself DOWN stays DOWN whatever the override
+        let mut before = before;
+        before.status = Status::Down;
+        let mut after = before.clone();
+        after.overriddenstatus = Some(Status::OutOfService);
+        assert!(diff(&snapshot(&[&before]), &snapshot(&[&after])).is_empty());
+    }
+
+    #[test]
+    fn test_diff_address_change() {
+        let before = build_test_instance();
+        let mut after = before.clone();
+        after.port = Some(8080);
+
+        let events = diff(&snapshot(&[&before]), &snapshot(&[&after]));
+        assert_eq!(
+            vec![RegistryEvent::Removed(before), RegistryEvent::Added(after)],
+            events
+        );
+        assert!(diff(&snapshot(&[]), &snapshot(&[])).is_empty());
+    }
+}
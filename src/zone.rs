@@ -0,0 +1,261 @@
+use crate::load_balancer::LoadBalancer;
+use crate::response::Instance;
+use std::collections::HashMap;
+
+/// The number of healthy same-zone instances needed before other zones are ignored
+pub const DEFAULT_AFFINITY_THRESHOLD: usize = 1;
+
+/// Zone configuration for the client, following Spring Cloud's `availabilityZones`
+/// and `serviceUrl.<zone>` properties.
+///
+/// Eureka servers in the local zone are tried first, followed by the zones after it in
+/// `availabilityZones`, wrapping around to the ones before it, as Spring Cloud does.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ZoneConfig {
+    /// The zone this client runs in
+    pub local_zone: Option<String>,
+    /// The zones of the region, in preference order
+    pub availability_zones: Vec<String>,
+    /// The Eureka server urls of each zone
+    pub service_urls: HashMap<String, Vec<String>>,
+    /// The minimum number of healthy local zone instances needed before
+    /// discovery stops falling back to other zones
+    pub affinity_threshold: usize,
+}
+
+impl ZoneConfig {
+    /// Creates a new, empty, zone configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `local_zone` - The zone this client runs in, e.g. from
+    ///   [DataCenterInfo::zone](../request/struct.DataCenterInfo.html#method.zone)
+    pub fn new(local_zone: Option<&str>) -> ZoneConfig {
+        ZoneConfig {
+            local_zone: local_zone.map(str::to_owned),
+            availability_zones: Vec::new(),
+            service_urls: HashMap::new(),
+            affinity_threshold: DEFAULT_AFFINITY_THRESHOLD,
+        }
+    }
+
+    /// Adds a Eureka server url for a zone, registering the zone if it is new
+    pub fn service_url(mut self, zone: &str, url: &str) -> ZoneConfig {
+        if !self.availability_zones.iter().any(|z| z == zone) {
+            self.availability_zones.push(zone.to_owned());
+        }
+        self.service_urls
+            .entry(zone.to_owned())
+            .or_default()
+            .push(url.to_owned());
+        self
+    }
+
+    pub fn affinity_threshold(mut self, threshold: usize) -> ZoneConfig {
+        self.affinity_threshold = threshold;
+        self
+    }
+
+    /// The zones in the order their servers should be tried: the local zone, then the
+    /// zones after it in `availability_zones`, wrapping around, then any zone that only
+    /// appears in `service_urls`
+    pub fn ordered_zones(&self) -> Vec<&str> {
+        let mut zones = self
+            .availability_zones
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let local = self.local_zone.as_deref();
+        if let Some(index) = zones.iter().position(|z| Some(*z) == local) {
+            zones.rotate_left(index);
+        }
+
+        let mut unlisted = self
+            .service_urls
+            .keys()
+            .map(String::as_str)
+            .filter(|z| !zones.contains(z))
+            .collect::<Vec<_>>();
+        unlisted.sort_unstable();
+        // a local zone that only has service urls still comes first
+        if let Some(index) = unlisted.iter().position(|z| Some(*z) == local) {
+            zones.insert(0, unlisted.remove(index));
+        }
+        zones.extend(unlisted);
+        zones
+    }
+
+    /// Every configured Eureka server url, with the local zone's servers first
+    pub fn ordered_service_urls(&self) -> Vec<String> {
+        self.ordered_zones()
+            .into_iter()
+            .filter_map(|z| self.service_urls.get(z))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Narrows the instances to those in the local zone, when at least
+    /// `affinity_threshold` of them are there. Otherwise every instance is kept.
+    pub fn filter_instances<'a>(&self, instances: &[&'a Instance]) -> Vec<&'a Instance> {
+        let local = match self.local_zone.as_deref() {
+            Some(z) => z,
+            None => return instances.to_vec(),
+        };
+        let same_zone = instances
+            .iter()
+            .filter(|i| i.zone() == Some(local))
+            .copied()
+            .collect::<Vec<_>>();
+        if !same_zone.is_empty() && same_zone.len() >= self.affinity_threshold {
+            same_zone
+        } else {
+            debug!(
+                "only {} instances in zone {}, falling back to all zones",
+                same_zone.len(),
+                local
+            );
+            instances.to_vec()
+        }
+    }
+}
+
+/// Wraps a [LoadBalancer](../load_balancer/trait.LoadBalancer.html), preferring
+/// instances in the client's own zone
+pub struct ZoneAffinityLoadBalancer<L> {
+    zones: ZoneConfig,
+    inner: L,
+}
+
+impl<L: LoadBalancer> ZoneAffinityLoadBalancer<L> {
+    pub fn new(zones: ZoneConfig, inner: L) -> ZoneAffinityLoadBalancer<L> {
+        ZoneAffinityLoadBalancer { zones, inner }
+    }
+}
+
+impl<L: LoadBalancer> LoadBalancer for ZoneAffinityLoadBalancer<L> {
+    fn choose<'a>(&self, candidates: &[&'a Instance]) -> Option<&'a Instance> {
+        self.inner.choose(&self.zones.filter_instances(candidates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_balancer::RoundRobinLoadBalancer;
+    use crate::response::{build_test_instance, Application, Status};
+
+    fn build_test_zone_config() -> ZoneConfig {
+        ZoneConfig::new(Some("us-east-1b"))
+            .service_url("us-east-1a", "http://a1")
+            .service_url("us-east-1b", "http://b1")
+            .service_url("us-east-1c", "http://c1")
+            .service_url("us-east-1b", "http://b2")
+    }
+
+    fn build_test_instance_in(host: &str, zone: &str) -> Instance {
+        let mut instance = build_test_instance();
+        instance.host_name = host.to_owned();
+        if let Some(amazon) = instance.data_center_info.metadata.as_mut() {
+            amazon.availability_zone = zone.to_owned();
+        }
+        instance
+    }
+
+    #[test]
+    fn test_ordered_service_urls() {
+        let zones = build_test_zone_config();
+        assert_eq!(
+            vec!["us-east-1b", "us-east-1c", "us-east-1a"],
+            zones.ordered_zones()
+        );
+        assert_eq!(
+            vec!["http://b1", "http://b2", "http://c1", "http://a1"],
+            zones.ordered_service_urls()
+        );
+    }
+
+    #[test]
+    fn test_ordered_zones_local_in_the_middle() {
+        let mut zones = build_test_zone_config()
+            .service_url("us-east-1d", "http://d1")
+            .service_url("us-east-1e", "http://e1");
+        zones.availability_zones.retain(|z| z != "us-east-1e");
+        assert_eq!(
+            vec![
+                "us-east-1b",
+                "us-east-1c",
+                "us-east-1d",
+                "us-east-1a",
+                "us-east-1e"
+            ],
+            zones.ordered_zones()
+        );
+
+        // a local zone missing from availability_zones
+        zones.local_zone = Some("us-east-1e".to_owned());
+        assert_eq!(
+            vec![
+                "us-east-1e",
+                "us-east-1a",
+                "us-east-1b",
+                "us-east-1c",
+                "us-east-1d"
+            ],
+            zones.ordered_zones()
+        );
+    }
+
+    #[test]
+    fn test_ordered_service_urls_unknown_zone() {
+        let mut zones = build_test_zone_config();
+        zones.local_zone = Some("eu-west-1a".to_owned());
+        zones.availability_zones.retain(|z| z != "us-east-1c");
+        assert_eq!(
+            vec!["us-east-1a", "us-east-1b", "us-east-1c"],
+            zones.ordered_zones()
+        );
+    }
+
+    #[test]
+    fn test_filter_instances() {
+        let a = build_test_instance_in("a", "us-east-1a");
+        let b = build_test_instance_in("b", "us-east-1b");
+        let zones = build_test_zone_config();
+
+        let filtered = zones.filter_instances(&[&a, &b]);
+        assert_eq!(1, filtered.len());
+        assert_eq!("b", filtered[0].host_name);
+
+        let zones = zones.affinity_threshold(2);
+        assert_eq!(2, zones.filter_instances(&[&a, &b]).len());
+        assert_eq!(1, zones.filter_instances(&[&a]).len());
+    }
+
+    #[test]
+    fn test_filter_instances_metadata_zone() {
+        let a = build_test_instance_in("a", "us-east-1a");
+        let mut b = build_test_instance_in("b", "");
+        b.data_center_info.metadata = None;
+        b.metadata
+            .insert("zone".to_owned(), serde_json::Value::from("us-east-1b"));
+
+        let filtered = build_test_zone_config().filter_instances(&[&a, &b]);
+        assert_eq!(1, filtered.len());
+        assert_eq!("b", filtered[0].host_name);
+    }
+
+    #[test]
+    fn test_zone_affinity_load_balancer() {
+        let mut down = build_test_instance_in("b-down", "us-east-1b");
+        down.status = Status::Down;
+        let app = Application {
+            name: "test_app".into(),
+            instance: vec![build_test_instance_in("a", "us-east-1a"), down],
+        };
+        let lb =
+            ZoneAffinityLoadBalancer::new(build_test_zone_config(), RoundRobinLoadBalancer::new());
+        // the only local instance is down, so the other zone is used
+        assert_eq!("a", lb.select(&app).expect("an instance").host_name);
+    }
+}
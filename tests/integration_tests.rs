@@ -61,18 +61,20 @@ fn build_test_instance(app_name: &str, port: Option<u16>) -> Instance {
         data_center_info: DataCenterInfo {
             name: DcName::MyOwn,
             metadata: Some(AmazonMetaData {
-                ami_launch_index: "001".to_owned(),
+                ami_launch_index: Some("001".to_owned()),
                 local_hostname: "localhost".to_owned(),
                 availability_zone: "us-east-1a".to_owned(),
                 instance_id: "i-test001".to_owned(),
-                public_ip4: "127.0.0.1".to_owned(),
-                public_hostname: "localhost".to_owned(),
-                ami_manifest_path: "/test/path".to_owned(),
+                public_ip4: Some("127.0.0.1".to_owned()),
+                public_hostname: Some("localhost".to_owned()),
+                ami_manifest_path: Some("/test/path".to_owned()),
                 local_ip4: "127.0.0.1".to_owned(),
-                hostname: "localhost".to_owned(),
+                hostname: Some("localhost".to_owned()),
                 ami_id: "ami-test123".to_owned(),
                 instance_type: "t2.micro".to_owned(),
+                ..Default::default()
             }),
+            custom_metadata: None,
         },
         lease_info: Some(LeaseInfo {
             eviction_duration_in_secs: Some(90),
@@ -147,7 +149,10 @@ async fn test_register_instance() {
     );
 
     let app = app_result.expect("get application should succeed");
-    assert_eq!(app.application.name, app_name, "Application name mismatch");
+    assert_eq!(
+        &*app.application.name, app_name,
+        "Application name mismatch"
+    );
     println!("✓ Verified instance is registered");
 }
 
@@ -177,7 +182,7 @@ async fn test_get_application() {
     assert!(result.is_ok(), "Failed to get application: {:?}", result);
 
     let app_response = result.expect("get application should succeed");
-    assert_eq!(app_response.application.name, app_name);
+    assert_eq!(&*app_response.application.name, app_name);
     println!("✓ Successfully retrieved application");
 }
 
@@ -220,7 +225,7 @@ async fn test_get_all_applications() {
     );
 
     // Verify our app is in the list
-    let found = apps.iter().any(|app| app.name == app_name);
+    let found = apps.iter().any(|app| *app.name == *app_name);
     assert!(found, "Our registered app {} not found in list", app_name);
     println!("✓ Our application is in the registry");
 }
@@ -470,7 +475,7 @@ async fn test_full_lifecycle() {
         .applications
         .applications
         .iter()
-        .any(|a| a.name == app_name);
+        .any(|a| *a.name == *app_name);
     assert!(found, "Our app not found in registry");
     println!("   ✓ Our application is in the registry");
 
@@ -538,7 +543,7 @@ async fn test_full_applications() {
         .applications
         .applications
         .iter()
-        .any(|a| a.name == app_name);
+        .any(|a| *a.name == *app_name);
     assert!(found, "Our app not found in registry");
     println!("   ✓ Our application is in the registry");
 
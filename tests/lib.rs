@@ -1,7 +1,11 @@
 use httpmock::MockServer;
+use rust_eureka::ec2_metadata::Ec2MetadataProvider;
+use rust_eureka::errors::EurekaClientError;
+use rust_eureka::gcp_metadata::GcpMetadataProvider;
 use rust_eureka::request::{
     AmazonMetaData, DataCenterInfo, DcName, Instance, RegisterRequest, Status,
 };
+use rust_eureka::zone::ZoneConfig;
 use rust_eureka::EurekaClient;
 use serde_json::Map;
 
@@ -36,6 +40,1550 @@ async fn test_register_with_mock_server() {
     m1.assert_async().await;
 }
 
+#[tokio::test]
+async fn test_register_rejects_invalid_instance() {
+    let server = MockServer::start_async().await;
+    let m1 = server
+        .mock_async(|when, then| {
+            when.method("POST").path_includes("/apps/");
+            then.status(204);
+        })
+        .await;
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created");
+    let mut request = build_test_register_request();
+    request.instance.ip_addr = "not-an-ip".to_owned();
+
+    let result = client.register(EUREKA_CLIENT, &request).await;
+    match result {
+        Err(EurekaClientError::ValidationError(errors)) => assert_eq!(1, errors.len()),
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+
+    // nothing should have been sent to the server
+    m1.assert_calls_async(0).await;
+}
+
+#[tokio::test]
+async fn test_register_sends_metadata() {
+    let server = MockServer::start_async().await;
+    let m1 = server
+        .mock_async(|when, then| {
+            when.method("POST")
+                .path_includes("/apps/")
+                .header("content-type", "application/xml")
+                .body_includes("<ami-id>232332</ami-id>")
+                .body_includes("<metadata><team>a &amp; b</team></metadata>")
+                .body_includes("<name>Custom &lt;dc&gt;</name>");
+            then.status(204);
+        })
+        .await;
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created");
+    let mut request = build_test_register_request();
+    request.instance.data_center_info.name = DcName::Custom("Custom <dc>".to_owned());
+    request
+        .instance
+        .metadata
+        .insert("team".to_owned(), serde_json::Value::from("a & b"));
+
+    let result = client.register(EUREKA_CLIENT, &request).await;
+    assert!(result.is_ok());
+    m1.assert_async().await;
+
+    // metadata keys that cannot be XML element names are registered as JSON
+    let m2 = server
+        .mock_async(|when, then| {
+            when.method("POST")
+                .path_includes("/apps/")
+                .header("content-type", "application/json;charset=UTF-8")
+                .body_includes("\"1st team\":\"x\"");
+            then.status(204);
+        })
+        .await;
+    request
+        .instance
+        .metadata
+        .insert("1st team".to_owned(), serde_json::Value::from("x"));
+    let result = client.register(EUREKA_CLIENT, &request).await;
+    assert!(result.is_ok());
+    m2.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_register_prefers_local_zone() {
+    let local = MockServer::start_async().await;
+    let remote = MockServer::start_async().await;
+    let local_register = local
+        .mock_async(|when, then| {
+            when.method("POST").path_includes("/apps/");
+            then.status(204);
+        })
+        .await;
+    let remote_register = remote
+        .mock_async(|when, then| {
+            when.method("POST").path_includes("/apps/");
+            then.status(204);
+        })
+        .await;
+
+    let zones = ZoneConfig::new(Some("zone-b"))
+        .service_url("zone-a", &remote.base_url())
+        .service_url("zone-b", &local.base_url());
+    let client = EurekaClient::with_zones(EUREKA_CLIENT, &zones).expect("client should be created");
+    let result = client
+        .register(EUREKA_CLIENT, &build_test_register_request())
+        .await;
+    assert!(result.is_ok());
+
+    local_register.assert_async().await;
+    remote_register.assert_calls_async(0).await;
+}
+
+#[tokio::test]
+async fn test_register_falls_back_to_other_zone() {
+    // the local server has no mocks, so every registration attempt is a 404
+    let local = MockServer::start_async().await;
+    let remote = MockServer::start_async().await;
+    let remote_register = remote
+        .mock_async(|when, then| {
+            when.method("POST")
+                .path_includes("/apps/")
+                .header("content-type", "application/xml");
+            then.status(204);
+        })
+        .await;
+
+    let zones = ZoneConfig::new(Some("zone-b"))
+        .service_url("zone-a", &remote.base_url())
+        .service_url("zone-b", &local.base_url());
+    let client = EurekaClient::with_zones(EUREKA_CLIENT, &zones).expect("client should be created");
+    let result = client
+        .register(EUREKA_CLIENT, &build_test_register_request())
+        .await;
+    assert!(result.is_ok());
+
+    remote_register.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_fetch_fails_over_on_server_errors() {
+    let local = MockServer::start_async().await;
+    let remote = MockServer::start_async().await;
+    let unavailable = local
+        .mock_async(|when, then| {
+            when.method("GET");
+            then.status(503).body("unavailable");
+        })
+        .await;
+    let registry_body = format!(
+        r#"{{"applications":{{"versions__delta":"1","apps__hashcode":"UP_1_","application":[{{"name":"INVENTORY-SERVICE","instance":[{}]}}]}}}}"#,
+        instance_json(8080)
+    );
+    remote
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(registry_body);
+        })
+        .await;
+    let app_body = format!(
+        r#"{{"application":{{"name":"INVENTORY-SERVICE","instance":[{}]}}}}"#,
+        instance_json(8080)
+    );
+    remote
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps/INVENTORY-SERVICE");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(app_body);
+        })
+        .await;
+
+    let zones = ZoneConfig::new(Some("zone-b"))
+        .service_url("zone-a", &remote.base_url())
+        .service_url("zone-b", &local.base_url());
+    let client = EurekaClient::with_zones(EUREKA_CLIENT, &zones).expect("client should be created");
+    let (applications, source) = client
+        .get_applications_with_source()
+        .await
+        .expect("the other zone should answer");
+    assert_eq!(1, applications.applications.applications.len());
+    assert!(source.as_str().starts_with(&remote.base_url()));
+    let application = client
+        .get_application("INVENTORY-SERVICE")
+        .await
+        .expect("the other zone should answer");
+    assert_eq!(1, application.application.instance.len());
+    assert!(unavailable.calls_async().await > 0);
+
+    // only failing servers give the server error back
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &local.base_url()).expect("client should be created");
+    assert!(matches!(
+        client.get_applications().await,
+        Err(EurekaClientError::InternalServerError)
+    ));
+}
+
+#[tokio::test]
+async fn test_health_reporter_pushes_status_changes() {
+    use rust_eureka::health::HealthReporter;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let server = MockServer::start_async().await;
+    let down = server
+        .mock_async(|when, then| {
+            when.method("PUT")
+                .path(format!("/v2/apps/{}/localhost/status", EUREKA_CLIENT))
+                .query_param("value", "DOWN");
+            then.status(200);
+        })
+        .await;
+    let register = server
+        .mock_async(|when, then| {
+            when.method("POST").path_includes("/apps/");
+            then.status(204);
+        })
+        .await;
+
+    let client = Arc::new(
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created"),
+    );
+    let reporter = HealthReporter::new(client, build_test_register_request(), || async {
+        Status::Down
+    })
+    .interval(Duration::from_millis(10))
+    .debounce(2)
+    .spawn();
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    reporter.abort();
+
+    // reported once through the status endpoint, after two consecutive DOWN checks
+    down.assert_async().await;
+    register.assert_calls_async(0).await;
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown_marks_and_deregisters() {
+    use rust_eureka::shutdown::{GracefulShutdown, ShutdownOutcome};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let server = MockServer::start_async().await;
+    let out_of_service = server
+        .mock_async(|when, then| {
+            when.method("PUT")
+                .path(format!("/v2/apps/{}/localhost/status", EUREKA_CLIENT))
+                .query_param("value", "OUT_OF_SERVICE");
+            then.status(200);
+        })
+        .await;
+    let deregister = server
+        .mock_async(|when, then| {
+            when.method("DELETE")
+                .path_includes(format!("/apps/{}/localhost", EUREKA_CLIENT));
+            then.status(200);
+        })
+        .await;
+
+    let client = Arc::new(
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created"),
+    );
+    let outcome = GracefulShutdown::new(client, build_test_register_request())
+        .drain_period(Duration::ZERO)
+        .shutdown()
+        .await;
+
+    assert!(matches!(outcome, ShutdownOutcome::Deregistered));
+    out_of_service.assert_async().await;
+    deregister.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown_deadline() {
+    use rust_eureka::shutdown::{GracefulShutdown, ShutdownOutcome};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method("PUT").path_includes("/status");
+            then.status(200);
+        })
+        .await;
+    server
+        .mock_async(|when, then| {
+            when.method("DELETE").path_includes("/apps/");
+            then.status(200).delay(Duration::from_secs(2));
+        })
+        .await;
+
+    let client = Arc::new(
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created"),
+    );
+    let outcome = GracefulShutdown::new(client, build_test_register_request())
+        .drain_period(Duration::ZERO)
+        .deadline(Duration::from_millis(100))
+        .shutdown()
+        .await;
+
+    assert!(matches!(outcome, ShutdownOutcome::TimedOut));
+}
+
+#[tokio::test]
+async fn test_register_starting_then_ready() {
+    use rust_eureka::readiness::ReadyOutcome;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let server = MockServer::start_async().await;
+    let starting = server
+        .mock_async(|when, then| {
+            when.method("POST")
+                .path_includes("/apps/")
+                .body_includes("<status>STARTING</status>");
+            then.status(204);
+        })
+        .await;
+    let up = server
+        .mock_async(|when, then| {
+            when.method("PUT")
+                .path(format!("/v2/apps/{}/localhost/status", EUREKA_CLIENT))
+                .query_param("value", "UP");
+            then.status(200);
+        })
+        .await;
+    let body = format!(
+        r#"{{"application":{{"name":"{}","instance":[{}]}}}}"#,
+        EUREKA_CLIENT,
+        instance_json(8080).replace(r#""hostName":"127.0.0.1""#, r#""hostName":"localhost""#)
+    );
+    let lookup = server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path(format!("/v2/apps/{}", EUREKA_CLIENT));
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let client = Arc::new(
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created"),
+    );
+    let readiness = client
+        .register_starting(EUREKA_CLIENT, build_test_register_request())
+        .await
+        .expect("registration should succeed");
+    let outcome = readiness
+        .ready_when(async {})
+        .await
+        .expect("status update should succeed");
+    assert_eq!(ReadyOutcome::Ready, outcome);
+    readiness
+        .await_visible(Duration::from_secs(1), Duration::from_millis(10))
+        .await
+        .expect("instance should be visible");
+
+    starting.assert_async().await;
+    up.assert_async().await;
+    // once for the override check, once for visibility
+    lookup.assert_calls_async(2).await;
+}
+
+#[tokio::test]
+async fn test_mark_ready_respects_remote_override() {
+    use rust_eureka::readiness::ReadyOutcome;
+    use std::sync::Arc;
+
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method("POST").path_includes("/apps/");
+            then.status(204);
+        })
+        .await;
+    let up = server
+        .mock_async(|when, then| {
+            when.method("PUT").path_includes("/status");
+            then.status(200);
+        })
+        .await;
+    let body = format!(
+        r#"{{"application":{{"name":"{}","instance":[{}]}}}}"#,
+        EUREKA_CLIENT,
+        instance_json(8080).replace(
+            r#""status":"UP""#,
+            r#""status":"STARTING","overriddenstatus":"OUT_OF_SERVICE""#
+        )
+    );
+    server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path(format!("/v2/apps/{}", EUREKA_CLIENT));
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let client = Arc::new(
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created"),
+    );
+    let readiness = client
+        .register_starting(EUREKA_CLIENT, build_test_register_request())
+        .await
+        .expect("registration should succeed")
+        .instance_id("127.0.0.1");
+    let outcome = readiness
+        .mark_ready()
+        .await
+        .expect("mark_ready should succeed");
+    assert_eq!(
+        ReadyOutcome::Overridden(rust_eureka::response::Status::OutOfService),
+        outcome
+    );
+
+    up.assert_calls_async(0).await;
+}
+
+#[tokio::test]
+async fn test_await_visible_ignores_overridden_instance() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method("POST").path_includes("/apps/");
+            then.status(204);
+        })
+        .await;
+    let body = format!(
+        r#"{{"application":{{"name":"{}","instance":[{}]}}}}"#,
+        EUREKA_CLIENT,
+        instance_json(8080).replace(
+            r#""status":"UP""#,
+            r#""status":"UP","overriddenstatus":"OUT_OF_SERVICE""#
+        )
+    );
+    server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path(format!("/v2/apps/{}", EUREKA_CLIENT));
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let client = Arc::new(
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created"),
+    );
+    let readiness = client
+        .register_starting(EUREKA_CLIENT, build_test_register_request())
+        .await
+        .expect("registration should succeed")
+        .instance_id("127.0.0.1");
+    assert!(readiness
+        .await_visible(Duration::from_millis(50), Duration::from_millis(10))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_ec2_metadata_with_mock_server() {
+    let server = MockServer::start_async().await;
+
+    let token = server
+        .mock_async(|when, then| {
+            when.method("PUT")
+                .path("/latest/api/token")
+                .header_exists("X-aws-ec2-metadata-token-ttl-seconds");
+            then.status(200).body("test-token");
+        })
+        .await;
+    let public_ipv4 = server
+        .mock_async(|when, then| {
+            when.method("GET").path("/latest/meta-data/public-ipv4");
+            then.status(404);
+        })
+        .await;
+    let vpc_id = server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path("/latest/meta-data/network/interfaces/macs/10.0.0.12/vpc-id");
+            then.status(200).body("vpc-123");
+        })
+        .await;
+    let identity = server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path("/latest/dynamic/instance-identity/document");
+            then.status(200)
+                .body(r#"{"accountId":"123456789012","region":"us-east-1"}"#);
+        })
+        .await;
+    let meta_data = server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path_includes("/latest/meta-data/")
+                .header("X-aws-ec2-metadata-token", "test-token");
+            then.status(200).body("10.0.0.12");
+        })
+        .await;
+
+    let provider =
+        Ec2MetadataProvider::with_base_url(&server.base_url()).expect("provider should be created");
+    let mut instance = build_test_register_request().instance;
+    provider
+        .populate(&mut instance, true)
+        .await
+        .expect("metadata should be fetched");
+
+    assert_eq!(DcName::Amazon, instance.data_center_info.name);
+    let metadata = instance
+        .data_center_info
+        .metadata
+        .expect("metadata should be set");
+    assert_eq!("10.0.0.12", metadata.availability_zone);
+    assert_eq!(None, metadata.public_ip4);
+    assert_eq!(Some("vpc-123".to_owned()), metadata.vpc_id);
+    assert_eq!(Some("123456789012".to_owned()), metadata.account_id);
+    assert_eq!("10.0.0.12", instance.host_name);
+    assert_eq!("10.0.0.12", instance.ip_addr);
+
+    token.assert_async().await;
+    public_ipv4.assert_async().await;
+    vpc_id.assert_async().await;
+    identity.assert_async().await;
+    meta_data.assert_calls_async(12).await;
+}
+
+#[tokio::test]
+async fn test_gcp_metadata_with_mock_server() {
+    let server = MockServer::start_async().await;
+
+    let zone = server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path("/computeMetadata/v1/instance/zone")
+                .header("Metadata-Flavor", "Google");
+            then.status(200).body("projects/123456/zones/us-central1-a");
+        })
+        .await;
+    let project = server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path("/computeMetadata/v1/project/project-id");
+            then.status(200).body("my-project");
+        })
+        .await;
+    let id = server
+        .mock_async(|when, then| {
+            when.method("GET").path("/computeMetadata/v1/instance/id");
+            then.status(200).body("4567");
+        })
+        .await;
+
+    let provider =
+        GcpMetadataProvider::with_base_url(&server.base_url()).expect("provider should be created");
+    let mut instance = build_test_register_request().instance;
+    provider
+        .populate(&mut instance, true)
+        .await
+        .expect("metadata should be fetched");
+
+    assert_eq!(
+        DcName::Custom("GCP".to_owned()),
+        instance.data_center_info.name
+    );
+    let metadata = instance
+        .data_center_info
+        .custom_metadata
+        .expect("metadata should be set");
+    assert_eq!(Some("us-central1-a"), metadata["zone"].as_str());
+    assert_eq!(Some("us-central1"), metadata["region"].as_str());
+    assert_eq!(Some("my-project"), metadata["project-id"].as_str());
+    assert_eq!(Some("4567"), metadata["instance-id"].as_str());
+    // hostname and ip are not published by the stub, so they are left alone
+    assert_eq!("localhost", instance.host_name);
+
+    zone.assert_async().await;
+    project.assert_async().await;
+    id.assert_async().await;
+}
+
+#[cfg(feature = "middleware")]
+#[tokio::test]
+async fn test_middleware_resolves_service_name() {
+    use rust_eureka::load_balancer::RoundRobinLoadBalancer;
+    use rust_eureka::middleware::EurekaMiddleware;
+    use rust_eureka::registry::CachedRegistry;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    let eureka = MockServer::start_async().await;
+    let service = MockServer::start_async().await;
+
+    // a port nothing listens on, so the first instance refuses connections
+    let dead_port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("test unwrap")
+        .port();
+    let body = format!(
+        r#"{{"application":{{"name":"INVENTORY-SERVICE","instance":[{},{}]}}}}"#,
+        instance_json(dead_port),
+        instance_json(service.port())
+    );
+    let lookup = eureka
+        .mock_async(|when, then| {
+            when.method("GET").path_includes("/apps/INVENTORY-SERVICE");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+    let items = service
+        .mock_async(|when, then| {
+            when.method("GET").path("/items").query_param("page", "2");
+            then.status(200).body("[]");
+        })
+        .await;
+
+    let eureka_client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let registry = Arc::new(CachedRegistry::new(Arc::new(eureka_client)));
+    let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+        .with(EurekaMiddleware::new(
+            registry,
+            RoundRobinLoadBalancer::new(),
+        ))
+        .build();
+
+    for _ in 0..2 {
+        let res = client
+            .get("http://INVENTORY-SERVICE/items?page=2")
+            .send()
+            .await
+            .expect("request should be sent");
+        assert_eq!("[]", res.text().await.expect("test unwrap"));
+    }
+
+    // the second request is served from the registry cache
+    lookup.assert_async().await;
+    items.assert_calls_async(2).await;
+}
+
+#[cfg(feature = "middleware")]
+#[tokio::test]
+async fn test_middleware_ejects_outliers() {
+    use rust_eureka::load_balancer::RoundRobinLoadBalancer;
+    use rust_eureka::middleware::EurekaMiddleware;
+    use rust_eureka::outlier::OutlierDetector;
+    use rust_eureka::registry::CachedRegistry;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    let eureka = MockServer::start_async().await;
+    let service = MockServer::start_async().await;
+    let dead_port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("test unwrap")
+        .port();
+    let body = format!(
+        r#"{{"application":{{"name":"INVENTORY-SERVICE","instance":[{},{}]}}}}"#,
+        instance_json(dead_port),
+        instance_json(service.port())
+    );
+    eureka
+        .mock_async(|when, then| {
+            when.method("GET").path_includes("/apps/INVENTORY-SERVICE");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+    let items = service
+        .mock_async(|when, then| {
+            when.method("GET").path("/items");
+            then.status(200).body("[]");
+        })
+        .await;
+
+    let eureka_client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let registry = Arc::new(CachedRegistry::new(Arc::new(eureka_client)));
+    let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+        .with(
+            EurekaMiddleware::new(registry, RoundRobinLoadBalancer::new())
+                .max_retries(0)
+                .outlier_detector(OutlierDetector::new().consecutive_failures(1)),
+        )
+        .build();
+
+    // the first request hits the dead instance, which is ejected from then on
+    assert!(client
+        .get("http://INVENTORY-SERVICE/items")
+        .send()
+        .await
+        .is_err());
+    for _ in 0..2 {
+        let res = client
+            .get("http://INVENTORY-SERVICE/items")
+            .send()
+            .await
+            .expect("request should be sent");
+        assert_eq!("[]", res.text().await.expect("test unwrap"));
+    }
+    items.assert_calls_async(2).await;
+}
+
+#[cfg(feature = "middleware")]
+#[tokio::test]
+async fn test_middleware_returns_last_connection_error() {
+    use rust_eureka::load_balancer::RoundRobinLoadBalancer;
+    use rust_eureka::middleware::EurekaMiddleware;
+    use rust_eureka::registry::CachedRegistry;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    let eureka = MockServer::start_async().await;
+    let dead_port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("test unwrap")
+        .port();
+    let body = format!(
+        r#"{{"application":{{"name":"INVENTORY-SERVICE","instance":[{}]}}}}"#,
+        instance_json(dead_port)
+    );
+    eureka
+        .mock_async(|when, then| {
+            when.method("GET").path_includes("/apps/INVENTORY-SERVICE");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let eureka_client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let registry = Arc::new(CachedRegistry::new(Arc::new(eureka_client)));
+    let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+        .with(EurekaMiddleware::new(
+            registry,
+            RoundRobinLoadBalancer::new(),
+        ))
+        .build();
+
+    match client.get("http://INVENTORY-SERVICE/items").send().await {
+        Err(reqwest_middleware::Error::Reqwest(e)) => assert!(e.is_connect()),
+        other => panic!("expected a connection error, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "middleware")]
+#[tokio::test]
+async fn test_middleware_sends_unresolved_requests_unchanged() {
+    use rust_eureka::load_balancer::RoundRobinLoadBalancer;
+    use rust_eureka::middleware::EurekaMiddleware;
+    use rust_eureka::registry::CachedRegistry;
+    use std::sync::Arc;
+
+    let eureka = MockServer::start_async().await;
+    let service = MockServer::start_async().await;
+    eureka
+        .mock_async(|when, then| {
+            when.method("GET");
+            then.status(500).body("unavailable");
+        })
+        .await;
+    let items = service
+        .mock_async(|when, then| {
+            when.method("GET").path("/items");
+            then.status(200).body("[]");
+        })
+        .await;
+
+    let eureka_client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let registry = Arc::new(CachedRegistry::new(Arc::new(eureka_client)));
+    let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+        .with(EurekaMiddleware::new(
+            registry,
+            RoundRobinLoadBalancer::new(),
+        ))
+        .build();
+
+    let res = client
+        .get(format!("{}/items", service.base_url()))
+        .send()
+        .await
+        .expect("request should be sent");
+    assert_eq!("[]", res.text().await.expect("test unwrap"));
+    items.assert_async().await;
+}
+
+#[cfg(feature = "discover")]
+#[tokio::test]
+async fn test_discover_tracks_registry_changes() {
+    use futures::StreamExt;
+    use rust_eureka::discover::EurekaDiscover;
+    use rust_eureka::registry::CachedRegistry;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::discover::Change;
+
+    let eureka = MockServer::start_async().await;
+    let body = format!(
+        r#"{{"application":{{"name":"INVENTORY-SERVICE","instance":[{}]}}}}"#,
+        instance_json(8080)
+    );
+    let lookup = eureka
+        .mock_async(|when, then| {
+            when.method("GET").path_includes("/apps/INVENTORY-SERVICE");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let eureka_client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let registry = Arc::new(CachedRegistry::with_ttl(
+        Arc::new(eureka_client),
+        Duration::ZERO,
+    ));
+    let mut discover = EurekaDiscover::with_interval(
+        registry,
+        "INVENTORY-SERVICE",
+        Duration::from_millis(10),
+        |i| i.port,
+    );
+
+    match discover.next().await {
+        Some(Ok(Change::Insert(id, port))) => {
+            assert_eq!("127.0.0.1", id);
+            assert_eq!(Some(8080), port);
+        }
+        other => panic!("expected an insert, got {:?}", other),
+    }
+
+    // once the app is gone from eureka the instance is removed
+    lookup.delete_async().await;
+    match discover.next().await {
+        Some(Ok(Change::Remove(id))) => assert_eq!("127.0.0.1", id),
+        other => panic!("expected a remove, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "discover")]
+#[tokio::test]
+async fn test_discover_skips_ejected_instances() {
+    use futures::StreamExt;
+    use rust_eureka::discover::EurekaDiscover;
+    use rust_eureka::outlier::OutlierDetector;
+    use rust_eureka::registry::CachedRegistry;
+    use rust_eureka::response::Instance;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::discover::Change;
+
+    let with_id = |port: u16| {
+        instance_json(port).replace(
+            r#""hostName""#,
+            &format!(r#""instanceId":"127.0.0.1:{}","hostName""#, port),
+        )
+    };
+    let eureka = MockServer::start_async().await;
+    let body = format!(
+        r#"{{"application":{{"name":"INVENTORY-SERVICE","instance":[{},{}]}}}}"#,
+        with_id(8080),
+        with_id(8081)
+    );
+    eureka
+        .mock_async(|when, then| {
+            when.method("GET").path_includes("/apps/INVENTORY-SERVICE");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let detector = OutlierDetector::new().consecutive_failures(1);
+    let failing: Instance = serde_json::from_str(&with_id(8080)).expect("test unwrap");
+    detector.record_failure(&failing);
+
+    let eureka_client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let registry = Arc::new(CachedRegistry::new(Arc::new(eureka_client)));
+    let mut discover = EurekaDiscover::with_interval(
+        registry,
+        "INVENTORY-SERVICE",
+        Duration::from_millis(10),
+        |i| i.port,
+    )
+    .outlier_detector(detector);
+
+    match discover.next().await {
+        Some(Ok(Change::Insert(id, port))) => {
+            assert_eq!("127.0.0.1:8081", id);
+            assert_eq!(Some(8081), port);
+        }
+        other => panic!("expected an insert, got {:?}", other),
+    }
+    // the ejected instance is not handed out while ejected
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), discover.next())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_watch_reports_registry_events() {
+    use futures::StreamExt;
+    use rust_eureka::response::Status;
+    use rust_eureka::watch::{RegistryEvent, WatchConfig};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let eureka = MockServer::start_async().await;
+    let apps_json = |instance: String| {
+        format!(
+            r#"{{"applications":{{"versions__delta":"1","apps__hashcode":"UP_1_","application":[{{"name":"INVENTORY-SERVICE","instance":[{}]}}]}}}}"#,
+            instance
+        )
+    };
+    let up = apps_json(instance_json(8080));
+    let apps = eureka
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(up);
+        })
+        .await;
+
+    let client = Arc::new(
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created"),
+    );
+    let mut events = client.watch(
+        "inventory-service",
+        WatchConfig::new().interval(Duration::from_millis(10)),
+    );
+
+    match events.next().await {
+        Some(RegistryEvent::Added(instance)) => assert_eq!(Some(8080), instance.port),
+        other => panic!("expected an added event, got {:?}", other),
+    }
+
+    apps.delete_async().await;
+    let down = apps_json(instance_json(8080).replace(r#""UP""#, r#""DOWN""#));
+    eureka
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(down);
+        })
+        .await;
+
+    match events.next().await {
+        Some(RegistryEvent::StatusChanged { before, after }) => {
+            assert_eq!(Status::Up, before.status);
+            assert_eq!(Status::Down, after.status);
+        }
+        other => panic!("expected a status change, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_watch_delta_refetches_on_hashcode_mismatch() {
+    use futures::StreamExt;
+    use rust_eureka::response::Status;
+    use rust_eureka::watch::{FetchMode, RegistryEvent, WatchConfig};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let eureka = MockServer::start_async().await;
+    let apps_json = |hashcode: &str, instance: String| {
+        format!(
+            r#"{{"applications":{{"versions__delta":"1","apps__hashcode":"{}","application":[{{"name":"INVENTORY-SERVICE","instance":[{}]}}]}}}}"#,
+            hashcode, instance
+        )
+    };
+    let up = apps_json("UP_1_", instance_json(8080));
+    let apps = eureka
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(up);
+        })
+        .await;
+
+    let client = Arc::new(
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created"),
+    );
+    let mut events = client.watch(
+        "inventory-service",
+        WatchConfig::new()
+            .interval(Duration::from_millis(10))
+            .mode(FetchMode::Delta),
+    );
+
+    match events.next().await {
+        Some(RegistryEvent::Added(instance)) => assert_eq!(Some(8080), instance.port),
+        other => panic!("expected an added event, got {:?}", other),
+    }
+
+    // the delta turns the instance DOWN, but the server's registry still counts it UP,
+    // so the watch falls back to the full registry
+    apps.delete_async().await;
+    let down = apps_json(
+        "UP_1_",
+        instance_json(8080)
+            .replace(r#""UP""#, r#""DOWN""#)
+            .replace("ADDED", "MODIFIED"),
+    );
+    eureka
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps/delta");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(down);
+        })
+        .await;
+    let starting = apps_json(
+        "STARTING_1_",
+        instance_json(8080).replace(r#""UP""#, r#""STARTING""#),
+    );
+    let full = eureka
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(starting);
+        })
+        .await;
+
+    match events.next().await {
+        Some(RegistryEvent::StatusChanged { before, after }) => {
+            assert_eq!(Status::Up, before.status);
+            assert_eq!(Status::Starting, after.status);
+        }
+        other => panic!("expected a status change, got {:?}", other),
+    }
+    assert!(full.calls_async().await >= 1);
+}
+
+#[tokio::test]
+async fn test_stream_applications_filters_while_parsing() {
+    use rust_eureka::streaming::StreamOptions;
+
+    let eureka = MockServer::start_async().await;
+    let order = instance_json(9090).replace("INVENTORY-SERVICE", "ORDER-SERVICE");
+    let body = format!(
+        r#"{{"applications":{{"versions__delta":"2","apps__hashcode":"UP_2_DOWN_1_","application":[
+        {{"name":"INVENTORY-SERVICE","instance":[{},{}]}},{{"name":"ORDER-SERVICE","instance":[{}]}}]}}}}"#,
+        instance_json(8080),
+        instance_json(8081).replace(r#""UP""#, r#""DOWN""#),
+        order
+    );
+    eureka
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let applications = client
+        .stream_applications(StreamOptions::new().apps(&["inventory-service"]).up_only())
+        .await
+        .expect("request should succeed")
+        .into_applications()
+        .await
+        .expect("response should parse");
+
+    assert_eq!(2, applications.versions_delta);
+    assert_eq!("UP_2_DOWN_1_", applications.apps_hashcode);
+    assert_eq!(1, applications.applications.len());
+    let instances = &applications.applications[0].instance;
+    assert_eq!(1, instances.len());
+    assert_eq!(Some(8080), instances[0].port);
+
+    match client
+        .stream_applications(StreamOptions::new().max_response_size(100))
+        .await
+    {
+        Err(EurekaClientError::ResponseTooLarge(100)) => {}
+        Err(e) => panic!("expected the response to be too large, got {}", e),
+        Ok(_) => panic!("expected the response to be too large"),
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_registry_warm_start() {
+    use rust_eureka::persistence::SnapshotFile;
+    use rust_eureka::snapshot::SnapshotRegistry;
+
+    let eureka = MockServer::start_async().await;
+    let apps_json = |port: u16| {
+        format!(
+            r#"{{"applications":{{"versions__delta":"1","apps__hashcode":"UP_1_","application":[{{"name":"INVENTORY-SERVICE","instance":[{}]}}]}}}}"#,
+            instance_json(port)
+        )
+    };
+    let dir = tempfile::tempdir().expect("test unwrap");
+    let path = dir.path().join("registry.json");
+    std::fs::write(
+        &path,
+        format!(
+            r#"{{"header":{{"fetchedAt":1503442035871,"source":"http://old:8761/v2/apps"}},"response":{}}}"#,
+            apps_json(8080)
+        ),
+    )
+    .expect("test unwrap");
+
+    let registry = SnapshotRegistry::warm_start(SnapshotFile::new(&path)).await;
+    assert!(registry.is_stale());
+    assert_eq!(
+        Some(8080),
+        registry
+            .load()
+            .app("inventory-service")
+            .next()
+            .and_then(|i| i.port)
+    );
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    assert!(registry.refresh(&client).await.is_err());
+    assert!(registry.is_stale());
+
+    let body = apps_json(9090);
+    eureka
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+    registry
+        .refresh(&client)
+        .await
+        .expect("refresh should succeed");
+    assert!(!registry.is_stale());
+
+    let saved = SnapshotFile::new(&path)
+        .load()
+        .await
+        .expect("test unwrap")
+        .expect("a saved snapshot");
+    assert_eq!(
+        format!("{}/v2/apps", eureka.base_url()),
+        saved.header.source
+    );
+    assert_eq!(
+        Some(9090),
+        saved.response.applications.applications[0].instance[0].port
+    );
+}
+
+#[tokio::test]
+async fn test_cached_registry_caches_misses_and_failures() {
+    use rust_eureka::registry::CachedRegistry;
+    use std::sync::Arc;
+
+    let eureka = MockServer::start_async().await;
+    let lookup = eureka
+        .mock_async(|when, then| {
+            when.method("GET").path_includes("/apps/");
+            then.status(404);
+        })
+        .await;
+    let registry_body = format!(
+        r#"{{"applications":{{"versions__delta":"1","apps__hashcode":"UP_1_","application":[{{"name":"INVENTORY","instance":[{}]}}]}}}}"#,
+        instance_json(8080)
+    );
+    let all = eureka
+        .mock_async(|when, then| {
+            when.method("GET").path_matches(r"/apps/?$");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(registry_body);
+        })
+        .await;
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let registry = CachedRegistry::new(Arc::new(client));
+    for name in ["orders", "orders", "payments"] {
+        assert!(registry.resolve(name).await.expect("test unwrap").is_none());
+    }
+    // the full registry is fetched once for both unknown names
+    all.assert_async().await;
+    let lookups = lookup.calls_async().await;
+    assert!(registry
+        .resolve("orders")
+        .await
+        .expect("test unwrap")
+        .is_none());
+    assert_eq!(lookups, lookup.calls_async().await);
+
+    let failing = MockServer::start_async().await;
+    let lookup = failing
+        .mock_async(|when, then| {
+            when.method("GET");
+            then.status(500).body("unavailable");
+        })
+        .await;
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &failing.base_url()).expect("client should be created");
+    let registry = CachedRegistry::new(Arc::new(client));
+    assert!(registry.resolve("orders").await.is_err());
+    let lookups = lookup.calls_async().await;
+    assert!(registry.resolve("orders").await.is_err());
+    assert_eq!(lookups, lookup.calls_async().await);
+}
+
+#[tokio::test]
+async fn test_cached_registry_falls_back_to_backup() {
+    use rust_eureka::registry::CachedRegistry;
+    use rust_eureka::static_registry::StaticRegistry;
+    use std::sync::Arc;
+
+    let eureka = MockServer::start_async().await;
+    let lookup = eureka
+        .mock_async(|when, then| {
+            when.method("GET").path_includes("/apps/INVENTORY");
+            then.status(500).body("unavailable");
+        })
+        .await;
+
+    let backup = StaticRegistry::new()
+        .with_app("inventory", &["127.0.0.1:8080", "127.0.0.1:8081"])
+        .expect("test unwrap");
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &eureka.base_url()).expect("client should be created");
+    let registry = CachedRegistry::new(Arc::new(client)).with_backup(backup.clone());
+
+    let app = registry
+        .resolve("inventory")
+        .await
+        .expect("the backup should answer")
+        .expect("an application");
+    assert_eq!(2, app.instance.len());
+    assert!(lookup.calls_async().await > 0);
+
+    let offline = CachedRegistry::from_backup(backup);
+    assert!(offline
+        .resolve("inventory")
+        .await
+        .expect("test unwrap")
+        .is_some());
+    assert!(offline
+        .resolve("orders")
+        .await
+        .expect("test unwrap")
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_eureka_client_as_service_registry() {
+    use rust_eureka::service::{ServiceDiscovery, ServiceRegistry};
+
+    let server = MockServer::start_async().await;
+    let register = server
+        .mock_async(|when, then| {
+            when.method("POST")
+                .path(format!("/v2/apps/{}", EUREKA_CLIENT));
+            then.status(204);
+        })
+        .await;
+    let heartbeat = server
+        .mock_async(|when, then| {
+            when.method("PUT")
+                .path(format!("/v2/apps/{}/localhost", EUREKA_CLIENT));
+            then.status(200);
+        })
+        .await;
+    let body = format!(
+        r#"{{"application":{{"name":"INVENTORY-SERVICE","instance":[{}]}}}}"#,
+        instance_json(8080)
+    );
+    server
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps/INVENTORY-SERVICE");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created");
+    let registry: &dyn ServiceRegistry = &client;
+    registry
+        .register(&build_test_register_request())
+        .await
+        .expect("registration should succeed");
+    registry
+        .heartbeat(EUREKA_CLIENT, "localhost")
+        .await
+        .expect("heartbeat should succeed");
+
+    let discovery: &dyn ServiceDiscovery = &client;
+    let instances = discovery
+        .instances("INVENTORY-SERVICE")
+        .await
+        .expect("lookup should succeed");
+    assert_eq!(1, instances.len());
+    // everything else 404s
+    assert!(discovery
+        .instances("UNKNOWN")
+        .await
+        .expect("test unwrap")
+        .is_empty());
+    assert!(matches!(
+        registry.heartbeat(EUREKA_CLIENT, "gone").await,
+        Err(EurekaClientError::NotFound)
+    ));
+
+    register.assert_async().await;
+    heartbeat.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_heartbeat_not_found_wins() {
+    let server = MockServer::start_async().await;
+    let register = server
+        .mock_async(|when, then| {
+            when.method("POST")
+                .path(format!("/v2/apps/{}", EUREKA_CLIENT));
+            then.status(204);
+        })
+        .await;
+    let not_found = server
+        .mock_async(|when, then| {
+            when.method("PUT")
+                .path(format!("/v2/apps/{}/localhost", EUREKA_CLIENT));
+            then.status(404);
+        })
+        .await;
+    server
+        .mock_async(|when, then| {
+            when.method("PUT");
+            then.status(503);
+        })
+        .await;
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created");
+    client
+        .register(EUREKA_CLIENT, &build_test_register_request())
+        .await
+        .expect("registration should succeed");
+    assert!(matches!(
+        client.heartbeat(EUREKA_CLIENT, "localhost").await,
+        Err(EurekaClientError::NotFound)
+    ));
+    register.assert_async().await;
+    not_found.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_heartbeat_unmounted_path_not_found() {
+    let server = MockServer::start_async().await;
+    let not_found = server
+        .mock_async(|when, then| {
+            when.method("PUT")
+                .path(format!("/eureka/v2/apps/{}/localhost", EUREKA_CLIENT));
+            then.status(404);
+        })
+        .await;
+    server
+        .mock_async(|when, then| {
+            when.method("PUT");
+            then.status(503);
+        })
+        .await;
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created");
+    assert!(matches!(
+        client.heartbeat(EUREKA_CLIENT, "localhost").await,
+        Err(EurekaClientError::InternalServerError)
+    ));
+    not_found.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_get_applications_in_regions() {
+    use rust_eureka::region::RegionConfig;
+
+    let in_zone = |port: u16, zone: &str| {
+        instance_json(port).replace(
+            r#""name":"MyOwn"}"#,
+            &format!(r#""name":"MyOwn","metadata":{{"zone":"{}"}}}}"#, zone),
+        )
+    };
+    let body = format!(
+        r#"{{"applications":{{"versions__delta":"1","apps__hashcode":"UP_3_","application":[{{"name":"INVENTORY-SERVICE","instance":[{},{},{}]}}]}}}}"#,
+        in_zone(8080, "us-west-2a"),
+        in_zone(8081, "us-east-1b"),
+        instance_json(8082)
+    );
+    let server = MockServer::start_async().await;
+    let apps = server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path("/v2/apps")
+                .query_param("regions", "us-west-2");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body.clone());
+        })
+        .await;
+    let delta = server
+        .mock_async(|when, then| {
+            when.method("GET")
+                .path("/v2/apps/delta")
+                .query_param("regions", "us-west-2");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        })
+        .await;
+
+    let client =
+        EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created");
+    let regions = RegionConfig::new("us-east-1").remote_region("us-west-2");
+    let applications = client
+        .get_applications_in_regions(&regions)
+        .await
+        .expect("fetch should succeed")
+        .applications
+        .applications;
+
+    // instances without a zone are local
+    assert_eq!(1, applications.len());
+    assert_eq!(
+        vec![
+            (Some(8081), Some("us-east-1")),
+            (Some(8082), Some("us-east-1")),
+            (Some(8080), Some("us-west-2")),
+        ],
+        applications[0]
+            .instance
+            .iter()
+            .map(|i| (i.port, i.region.as_deref()))
+            .collect::<Vec<_>>()
+    );
+
+    let delta_apps = client
+        .get_delta_in_regions(&regions)
+        .await
+        .expect("fetch should succeed");
+    assert_eq!(1, delta_apps.applications.applications.len());
+
+    apps.assert_async().await;
+    delta.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_federated_client() {
+    use rust_eureka::federation::FederatedClient;
+    use std::sync::Arc;
+
+    let body = |port: u16| {
+        format!(
+            r#"{{"applications":{{"versions__delta":"1","apps__hashcode":"UP_1_","application":[{{"name":"INVENTORY-SERVICE","instance":[{}]}}]}}}}"#,
+            instance_json(port)
+        )
+    };
+    let prod = MockServer::start_async().await;
+    prod.mock_async(|when, then| {
+        when.method("GET").path("/v2/apps");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(body(8080));
+    })
+    .await;
+    let staging = MockServer::start_async().await;
+    let staging_apps = staging
+        .mock_async(|when, then| {
+            when.method("GET").path("/v2/apps");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body(8081));
+        })
+        .await;
+
+    let client = |server: &MockServer| {
+        Arc::new(
+            EurekaClient::new(EUREKA_CLIENT, &server.base_url()).expect("client should be created"),
+        )
+    };
+    let federation = FederatedClient::new()
+        .cluster("prod", client(&prod))
+        .cluster("staging", client(&staging));
+
+    let merged = federation.fetch().await.expect("fetch should succeed");
+    assert_eq!(1, merged.applications.len());
+    assert_eq!("UP_2_", merged.apps_hashcode);
+    let clusters = merged.applications[0]
+        .instance
+        .iter()
+        .map(|i| (i.port, i.cluster.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![(Some(8080), Some("prod")), (Some(8081), Some("staging"))],
+        clusters
+    );
+
+    // staging goes down, its last registry is still served
+    staging_apps.delete_async().await;
+    staging
+        .mock_async(|when, then| {
+            when.method("GET");
+            then.status(500);
+        })
+        .await;
+    let merged = federation.fetch().await.expect("fetch should succeed");
+    assert_eq!(2, merged.applications[0].instance.len());
+    assert!(federation.health("prod").expect("test unwrap").is_healthy());
+    let health = federation.health("staging").expect("test unwrap");
+    assert!(!health.is_healthy());
+    assert_eq!(1, health.consecutive_failures);
+}
+
+fn instance_json(port: u16) -> String {
+    format!(
+        r#"{{"hostName":"127.0.0.1","app":"INVENTORY-SERVICE","ipAddr":"127.0.0.1",
+        "vipAddress":"inventory-service","secureVipAddress":"inventory-service","status":"UP",
+        "port":{{"$":{},"@enabled":"true"}},"homePageUrl":"http://127.0.0.1/",
+        "statusPageUrl":"http://127.0.0.1/status","healthCheckUrl":"http://127.0.0.1/health",
+        "dataCenterInfo":{{"@class":"com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo","name":"MyOwn"}},
+        "metadata":{{}},"countryId":1,"isCoordinatingDiscoveryServer":false,
+        "lastUpdatedTimestamp":1503442035871,"lastDirtyTimestamp":1503442035714,"actionType":"ADDED"}}"#,
+        port
+    )
+}
+
 #[test]
 fn output_json() {
     let request = build_test_register_request();
@@ -59,18 +1607,20 @@ fn build_test_register_request() -> RegisterRequest {
         data_center_info: DataCenterInfo {
             name: DcName::MyOwn,
             metadata: Some(AmazonMetaData {
-                ami_launch_index: "001".to_owned(),
+                ami_launch_index: Some("001".to_owned()),
                 local_hostname: "localhost".to_owned(),
                 availability_zone: "N/A".to_owned(),
                 instance_id: "001".to_owned(),
-                public_ip4: "127.0.0.1".to_owned(),
-                public_hostname: "localhost".to_owned(),
-                ami_manifest_path: "/a/path".to_owned(),
+                public_ip4: Some("127.0.0.1".to_owned()),
+                public_hostname: Some("localhost".to_owned()),
+                ami_manifest_path: Some("/a/path".to_owned()),
                 local_ip4: "127.0.0.1".to_owned(),
-                hostname: "localhost".to_owned(),
+                hostname: Some("localhost".to_owned()),
                 ami_id: "232332".to_owned(),
                 instance_type: "SomeType".to_owned(),
+                ..Default::default()
             }),
+            custom_metadata: None,
         },
         lease_info: None,
         metadata: Map::new(),